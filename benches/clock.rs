@@ -0,0 +1,58 @@
+use cache_rs::{CacheBuilder, Expiring};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const TASKS: usize = 8;
+const READS_PER_TASK: usize = 200_000;
+const KEYS: usize = 1_000;
+
+async fn run(granularity: Option<Duration>) -> Duration {
+    let mut builder = CacheBuilder::new()
+        .loader(|key: usize| {
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_secs(60))) })
+        })
+        .key_mapper(|key: &usize| key.to_string());
+    if let Some(granularity) = granularity {
+        builder = builder.clock_granularity(granularity);
+    }
+    let cache = Arc::new(builder.build());
+    for key in 0..KEYS {
+        cache.get(key).await.unwrap();
+    }
+
+    let start = Instant::now();
+    let mut handles = Vec::new();
+    for task in 0..TASKS {
+        let cache = cache.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..READS_PER_TASK {
+                cache.get((task + i) % KEYS).await.unwrap();
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(8)
+        .build()
+        .unwrap();
+
+    let ops = (TASKS * READS_PER_TASK) as f64;
+    for (label, granularity) in [
+        ("system clock", None),
+        ("coarse 100ms", Some(Duration::from_millis(100))),
+    ] {
+        let elapsed = runtime.block_on(run(granularity));
+        println!(
+            "{:<12}  elapsed={:>8.2?}  throughput={:>12.0} ops/s",
+            label,
+            elapsed,
+            ops / elapsed.as_secs_f64()
+        );
+    }
+}
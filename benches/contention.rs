@@ -0,0 +1,51 @@
+use cache_rs::{Cache, Expiring};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const TASKS: usize = 16;
+const KEYS_PER_TASK: usize = 20_000;
+
+async fn run(shard_count: usize) -> Duration {
+    let cache = Arc::new(Cache::with_shards(
+        |key: usize| {
+            Box::pin(async move { Ok(Expiring::with_duration(key * 2, Duration::from_secs(60))) })
+        },
+        |key: &usize| key.to_string(),
+        shard_count,
+    ));
+
+    let start = Instant::now();
+    let mut handles = Vec::new();
+    for task in 0..TASKS {
+        let cache = cache.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..KEYS_PER_TASK {
+                let key = task * KEYS_PER_TASK + i;
+                cache.get(key).await.unwrap();
+                cache.get(key).await.unwrap();
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(8)
+        .build()
+        .unwrap();
+
+    let ops = (TASKS * KEYS_PER_TASK * 2) as f64;
+    for shard_count in [1, 4, 16, 64] {
+        let elapsed = runtime.block_on(run(shard_count));
+        println!(
+            "shards={:>3}  elapsed={:>8.2?}  throughput={:>12.0} ops/s",
+            shard_count,
+            elapsed,
+            ops / elapsed.as_secs_f64()
+        );
+    }
+}
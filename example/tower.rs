@@ -0,0 +1,41 @@
+use cache_rs::CacheLayer;
+use std::future::{Ready, ready};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A stand-in for an expensive backend, such as an HTTP client
+#[derive(Clone)]
+struct Backend;
+
+impl Service<String> for Backend {
+    type Response = String;
+    type Error = std::convert::Infallible;
+    type Future = Ready<Result<String, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, path: String) -> Self::Future {
+        println!("  backend called for {}", path);
+        ready(Ok(format!("<contents of {}>", path)))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let layer = CacheLayer::new(Duration::from_secs(30), |path: &String| path.clone());
+    let mut service = layer.layer(Backend);
+
+    for path in ["/index.html", "/about.html", "/index.html"] {
+        println!("GET {}", path);
+        let response = service.call(path.to_string()).await?;
+        println!("  -> {}", response);
+    }
+
+    let stats = service.cache().stats();
+    println!("hits: {}, misses: {}", stats.hits, stats.misses);
+    Ok(())
+}
@@ -10,6 +10,12 @@ cargo clippy --lib --tests
 echo "🧪 Running tests..."
 cargo test
 
+echo "🧩 Checking feature combinations..."
+cargo clippy --all-targets --no-default-features -- -D warnings
+cargo test --no-default-features
+cargo clippy --all-targets --no-default-features --features async-std -- -D warnings
+cargo test --all-features
+
 echo "📚 Checking docs..."
 cargo doc --no-deps
 
@@ -0,0 +1,131 @@
+//! A slower second cache level behind the in-memory one
+
+use crate::cache::{BoxError, Expiring};
+use std::collections::HashMap;
+use std::future::{Future, ready};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// Future returned by [`BackingStore`] methods
+pub type BackingFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, BoxError>> + Send + 'a>>;
+
+/// A persistent store, such as Redis or disk, consulted between the cache and its loader
+///
+/// On a miss the cache asks the backing store for the identifier before
+/// calling the loader, and every value the loader produces is written to it.
+/// The backing store only ever speeds things up: a failed `get` falls back to
+/// the loader and a failed `set` or `delete` is skipped. A `get` takes a loader
+/// slot under `max_concurrent_loads` and is bounded by `load_timeout`, falling
+/// back to the loader if it runs out. Set one with
+/// [`CacheBuilder::backing_store`](crate::CacheBuilder::backing_store).
+pub trait BackingStore<V>: Send + Sync {
+    /// Looks up an entry, returning `None` if it isn't stored
+    fn get<'a>(&'a self, identifier: &'a str) -> BackingFuture<'a, Option<Expiring<V>>>;
+
+    /// Stores an entry, replacing any existing one
+    fn set<'a>(&'a self, identifier: &'a str, item: &'a Expiring<V>) -> BackingFuture<'a, ()>;
+
+    /// Removes an entry if it's stored
+    fn delete<'a>(&'a self, identifier: &'a str) -> BackingFuture<'a, ()>;
+}
+
+impl<V, B: BackingStore<V> + ?Sized> BackingStore<V> for Arc<B> {
+    fn get<'a>(&'a self, identifier: &'a str) -> BackingFuture<'a, Option<Expiring<V>>> {
+        (**self).get(identifier)
+    }
+
+    fn set<'a>(&'a self, identifier: &'a str, item: &'a Expiring<V>) -> BackingFuture<'a, ()> {
+        (**self).set(identifier, item)
+    }
+
+    fn delete<'a>(&'a self, identifier: &'a str) -> BackingFuture<'a, ()> {
+        (**self).delete(identifier)
+    }
+}
+
+/// A backing store that stores nothing, so every lookup goes to the loader
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoBackingStore;
+
+impl<V: Send + 'static> BackingStore<V> for NoBackingStore {
+    fn get<'a>(&'a self, _identifier: &'a str) -> BackingFuture<'a, Option<Expiring<V>>> {
+        Box::pin(ready(Ok(None)))
+    }
+
+    fn set<'a>(&'a self, _identifier: &'a str, _item: &'a Expiring<V>) -> BackingFuture<'a, ()> {
+        Box::pin(ready(Ok(())))
+    }
+
+    fn delete<'a>(&'a self, _identifier: &'a str) -> BackingFuture<'a, ()> {
+        Box::pin(ready(Ok(())))
+    }
+}
+
+/// A backing store kept in a plain map, meant for tests and as a reference
+#[derive(Debug)]
+pub struct MemoryBackingStore<V> {
+    entries: Mutex<HashMap<String, Expiring<V>>>,
+}
+
+impl<V> MemoryBackingStore<V> {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of entries stored, expired or not
+    pub fn len(&self) -> usize {
+        self.entries.lock().map_or(0, |entries| entries.len())
+    }
+
+    /// Whether nothing is stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<V> Default for MemoryBackingStore<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Clone + Send + 'static> BackingStore<V> for MemoryBackingStore<V> {
+    fn get<'a>(&'a self, identifier: &'a str) -> BackingFuture<'a, Option<Expiring<V>>> {
+        let item = self
+            .entries
+            .lock()
+            .map_err(|_| BoxError::from("backing store lock poisoned"))
+            .map(|entries| {
+                entries
+                    .get(identifier)
+                    .filter(|item| !item.is_expired())
+                    .cloned()
+            });
+        Box::pin(ready(item))
+    }
+
+    fn set<'a>(&'a self, identifier: &'a str, item: &'a Expiring<V>) -> BackingFuture<'a, ()> {
+        let stored = self
+            .entries
+            .lock()
+            .map_err(|_| BoxError::from("backing store lock poisoned"))
+            .map(|mut entries| {
+                entries.insert(identifier.to_string(), item.clone());
+            });
+        Box::pin(ready(stored))
+    }
+
+    fn delete<'a>(&'a self, identifier: &'a str) -> BackingFuture<'a, ()> {
+        let removed = self
+            .entries
+            .lock()
+            .map_err(|_| BoxError::from("backing store lock poisoned"))
+            .map(|mut entries| {
+                entries.remove(identifier);
+            });
+        Box::pin(ready(removed))
+    }
+}
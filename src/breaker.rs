@@ -0,0 +1,106 @@
+//! A circuit breaker that stops calling a failing loader for a while
+
+use crate::error::CacheError;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// When the cache stops calling its loader after repeated failures
+///
+/// Once `failure_threshold` loader calls fail within `window`, the circuit
+/// opens: loads fail fast with [`CacheError::CircuitOpen`] instead of calling
+/// the loader, or serve the stale value under
+/// [`ReloadErrorPolicy::StaleFallback`](crate::ReloadErrorPolicy::StaleFallback).
+/// After `cooldown` a single load is let through as a probe. If it succeeds
+/// the circuit closes again, otherwise it stays open for another `cooldown`.
+/// Hits are served as usual while the circuit is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    pub failure_threshold: usize,
+    pub window: Duration,
+    pub cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    /// Opens after `failure_threshold` failures within `window`, probing again after `cooldown`
+    pub fn new(failure_threshold: usize, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            window,
+            cooldown,
+        }
+    }
+}
+
+/// The breaker's state, shared by every load of one cache
+pub(crate) struct Breaker {
+    config: CircuitBreaker,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    /// When recent failures happened, oldest first, while closed
+    failures: VecDeque<Instant>,
+    /// When the circuit opened or the last probe was let through
+    opened_at: Option<Instant>,
+}
+
+impl Breaker {
+    pub(crate) fn new(config: CircuitBreaker) -> Self {
+        Self {
+            config,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Fails with [`CacheError::CircuitOpen`] unless a load may call the loader
+    ///
+    /// Once the cooldown has passed, the first caller becomes the probe and the
+    /// cooldown starts over for everyone else, so a probe that's dropped
+    /// before finishing only delays the next one.
+    pub(crate) fn check(&self) -> Result<(), CacheError> {
+        let Ok(mut state) = self.state.lock() else {
+            return Ok(());
+        };
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < self.config.cooldown => {
+                Err(CacheError::CircuitOpen)
+            }
+            Some(_) => {
+                state.opened_at = Some(Instant::now());
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records how a loader call went, opening or closing the circuit
+    pub(crate) fn record<T>(&self, result: &Result<T, CacheError>) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        if result.is_ok() {
+            state.failures.clear();
+            state.opened_at = None;
+            return;
+        }
+        if state.opened_at.is_some() {
+            state.opened_at = Some(now);
+            return;
+        }
+        while state
+            .failures
+            .front()
+            .is_some_and(|failed| now.duration_since(*failed) > self.config.window)
+        {
+            state.failures.pop_front();
+        }
+        state.failures.push_back(now);
+        if state.failures.len() >= self.config.failure_threshold.max(1) {
+            state.failures.clear();
+            state.opened_at = Some(now);
+        }
+    }
+}
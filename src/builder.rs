@@ -0,0 +1,460 @@
+use crate::backing::BackingStore;
+use crate::breaker::CircuitBreaker;
+use crate::cache::{
+    BoxError, Cache, CacheOptions, EvictionPolicy, Hooks, LoadFuture, PoisonPolicy,
+    ReloadErrorPolicy,
+};
+use crate::events::EvictionReason;
+use crate::key::{KeyMapper, TryKeyMapper};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use crate::retry::RetryPolicy;
+use std::hash::{BuildHasher, RandomState};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Fluent builder for a [`Cache`]
+///
+/// The loader and key mapper are tracked in the builder's type, so calling
+/// `build()` before both have been provided is a compile error rather than a
+/// runtime one.
+///
+/// ```rust
+/// use cache_rs::{CacheBuilder, Expiring};
+/// use std::time::Duration;
+///
+/// let cache = CacheBuilder::new()
+///     .loader(|key: u32| {
+///         Box::pin(async move {
+///             let value = format!("user_{}", key);
+///             Ok(Expiring::with_duration(value, Duration::from_secs(60)))
+///         })
+///     })
+///     .key_mapper(|key: &u32| key.to_string())
+///     .max_capacity(1_000)
+///     .default_ttl(Duration::from_secs(300))
+///     .sliding(Duration::from_secs(30))
+///     .negative_ttl(Duration::from_secs(5))
+///     .shards(32)
+///     .build();
+///
+/// assert_eq!(cache.shard_count(), 32);
+/// ```
+///
+/// Leaving out the key mapper doesn't compile:
+///
+/// ```compile_fail
+/// use cache_rs::{CacheBuilder, Expiring};
+///
+/// let cache = CacheBuilder::new()
+///     .loader(|key: u32| Box::pin(async move { Ok(Expiring::never(key)) }))
+///     .build();
+/// ```
+pub struct CacheBuilder<K, V, F, G, S = RandomState> {
+    load: F,
+    get_key_for_map: G,
+    options: CacheOptions,
+    hooks: Hooks<V>,
+    hasher: S,
+    _phantom: PhantomData<(K, V)>,
+}
+
+impl<K, V> CacheBuilder<K, V, (), ()> {
+    /// Creates a builder with default options and no loader or key mapper
+    pub fn new() -> Self {
+        Self {
+            load: (),
+            get_key_for_map: (),
+            options: CacheOptions::default(),
+            hooks: Hooks::default(),
+            hasher: RandomState::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K, V> Default for CacheBuilder<K, V, (), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, F, G, S> CacheBuilder<K, V, F, G, S> {
+    /// Sets the function used to load values on a miss
+    pub fn loader<L>(self, load: L) -> CacheBuilder<K, V, L, G, S>
+    where
+        L: Fn(K) -> LoadFuture<V>,
+    {
+        CacheBuilder {
+            load,
+            get_key_for_map: self.get_key_for_map,
+            options: self.options,
+            hooks: self.hooks,
+            hasher: self.hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sets the function that maps keys to their internal identifier
+    pub fn key_mapper<M>(self, get_key_for_map: M) -> CacheBuilder<K, V, F, M, S>
+    where
+        M: Fn(&K) -> String,
+    {
+        CacheBuilder {
+            load: self.load,
+            get_key_for_map,
+            options: self.options,
+            hooks: self.hooks,
+            hasher: self.hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sets a key mapper that can reject keys, see [`TryKeyMapper`]
+    pub fn try_key_mapper<M, E>(
+        self,
+        get_key_for_map: M,
+    ) -> CacheBuilder<K, V, F, TryKeyMapper<M>, S>
+    where
+        M: Fn(&K) -> Result<String, E>,
+        E: Into<BoxError>,
+    {
+        CacheBuilder {
+            load: self.load,
+            get_key_for_map: TryKeyMapper(get_key_for_map),
+            options: self.options,
+            hooks: self.hooks,
+            hasher: self.hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Hashes identifiers with `hasher` instead of the standard SipHash
+    ///
+    /// A faster non-cryptographic hasher can be a throughput win for trusted
+    /// keys, at the cost of the default's resistance to collision attacks.
+    pub fn with_hasher<H>(self, hasher: H) -> CacheBuilder<K, V, F, G, H>
+    where
+        H: BuildHasher + Clone,
+    {
+        CacheBuilder {
+            load: self.load,
+            get_key_for_map: self.get_key_for_map,
+            options: self.options,
+            hooks: self.hooks,
+            hasher,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Limits the cache to `capacity` entries, evicting the least recently used
+    pub fn max_capacity(mut self, capacity: usize) -> Self {
+        self.options.max_capacity = Some(capacity);
+        self
+    }
+
+    /// Allocates room for `initial` entries up front to avoid rehashing as the cache fills
+    ///
+    /// Independent of [`max_capacity`](Self::max_capacity), which caps how many
+    /// entries are kept.
+    pub fn initial_capacity(mut self, initial: usize) -> Self {
+        self.options.initial_capacity = initial;
+        self
+    }
+
+    /// Chooses which entries are evicted first when over capacity
+    ///
+    /// Defaults to [`EvictionPolicy::Lru`].
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.options.eviction_policy = policy;
+        self
+    }
+
+    /// Evicts `batch` entries at a time once the cache goes over `max_capacity`
+    ///
+    /// Going over then frees room for the next `batch - 1` inserts as well,
+    /// so the eviction scan runs once per batch instead of on every insert.
+    /// Defaults to one, which keeps the cache exactly at capacity. Zero is
+    /// treated as one.
+    pub fn eviction_batch(mut self, batch: usize) -> Self {
+        self.options.eviction_batch = batch;
+        self
+    }
+
+    /// Limits the total weight of cached values, evicting the least recently used
+    ///
+    /// `weigher` is called once when a value is inserted and its result is kept
+    /// with the entry. A single value heavier than `max_weight` is never cached:
+    /// it's still returned to the caller, but the next `get` loads it again.
+    pub fn with_weigher(
+        mut self,
+        weigher: impl Fn(&V) -> usize + Send + Sync + 'static,
+        max_weight: usize,
+    ) -> Self {
+        self.hooks.weigher = Some(Arc::new(weigher));
+        self.options.max_weight = Some(max_weight);
+        self
+    }
+
+    /// Sets the TTL used by [`Cache::put`]
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.options.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Extends an entry's expiry to at least `window` from now on every hit
+    pub fn sliding(mut self, window: Duration) -> Self {
+        self.options.sliding = Some(window);
+        self
+    }
+
+    /// Adds a random offset in `[0, max_jitter)` to the expiry of every loaded value
+    ///
+    /// Keys warmed together with the same TTL then expire spread over a window
+    /// instead of all reloading at once. Values passed to `insert` or `put` are
+    /// stored exactly as given.
+    pub fn with_jitter(mut self, max_jitter: Duration) -> Self {
+        self.options.jitter = Some(max_jitter);
+        self
+    }
+
+    /// Seeds the jitter's random offsets so they're reproducible
+    pub fn jitter_seed(mut self, seed: u64) -> Self {
+        self.options.jitter_seed = Some(seed);
+        self
+    }
+
+    /// Caps how long any entry lives after it's stored
+    ///
+    /// Sliding expiry, [`Cache::expire_at`] and long loader TTLs can't push an
+    /// entry past `max_age`, which bounds how stale cached data can get. Entries
+    /// that would never expire are given this as their expiry.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.options.max_age = Some(max_age);
+        self
+    }
+
+    /// Caches loader errors for `ttl` so repeated misses don't hammer the backend
+    ///
+    /// While a failure is cached, `get` returns it without calling the loader.
+    /// Because loader errors can't be cloned, every caller receives a
+    /// [`CacheError::Load`](crate::CacheError::Load) holding a
+    /// [`SharedError`](crate::SharedError) that wraps the original error; use
+    /// [`SharedError::inner`](crate::SharedError::inner) to downcast it.
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.options.negative_ttl = Some(ttl);
+        self
+    }
+
+    /// Stops loading a key for `cooldown` after its load fails
+    ///
+    /// The failing call gets the loader's error, and until `cooldown` passes
+    /// lookups of that key fail fast with [`crate::CacheError::InCooldown`]
+    /// saying how long is left, instead of calling a failing backend again.
+    /// Other keys still load as usual. Takes precedence over [`negative_ttl`](Self::negative_ttl).
+    pub fn failure_cooldown(mut self, cooldown: Duration) -> Self {
+        self.options.failure_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Registers a callback that runs whenever an entry leaves the cache
+    ///
+    /// The callback runs after the cache's locks are released, so it may call
+    /// back into the cache.
+    pub fn on_evict(
+        mut self,
+        on_evict: impl Fn(&str, &V, EvictionReason) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_evict = Some(Arc::new(on_evict));
+        self
+    }
+
+    /// Post-processes every loaded value before it's cached and returned
+    ///
+    /// Runs once per load, including background refreshes, but never on hits
+    /// or on values inserted directly. Useful for normalizing or compressing
+    /// values without repeating it in every loader.
+    pub fn transform(mut self, transform: impl Fn(V) -> V + Send + Sync + 'static) -> Self {
+        self.hooks.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Adjusts the TTL of every loaded value by how often its key is read
+    ///
+    /// `adaptive_ttl` gets the number of hits on the key since it was first
+    /// cached, counted across reloads, or zero for a key that isn't cached,
+    /// along with the TTL the loader returned, and gives back the TTL to cache
+    /// the value with. This lets hot keys live longer and cold ones expire
+    /// sooner. The count isn't aged like the LFU frequency. Runs on every
+    /// load, including background refreshes, after [`transform`](Self::transform)
+    /// and before jitter. Values that never expire are left alone.
+    pub fn adaptive_ttl(
+        mut self,
+        adaptive_ttl: impl Fn(u64, Duration) -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.adaptive_ttl = Some(Arc::new(adaptive_ttl));
+        self
+    }
+
+    /// Puts a slower, usually persistent store behind the cache, see [`BackingStore`]
+    ///
+    /// Misses check it before calling the loader, and loaded values are written
+    /// to it. Only the async paths touch it: `insert` and `delete` change the
+    /// in-memory level alone, while [`Cache::delete_await`] removes the key
+    /// from both.
+    pub fn backing_store(mut self, store: impl BackingStore<V> + 'static) -> Self {
+        self.hooks.backing = Some(Arc::new(store));
+        self
+    }
+
+    /// Fails a load with [`CacheError::LoadTimeout`](crate::CacheError::LoadTimeout) if the loader runs longer than `timeout`
+    ///
+    /// A timed-out load caches nothing, so the next `get` tries again.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub fn load_timeout(mut self, timeout: Duration) -> Self {
+        self.options.load_timeout = Some(timeout);
+        self
+    }
+
+    /// Retries failed loads up to `max_attempts` times in total, waiting `backoff` between them
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub fn with_retry(self, max_attempts: usize, backoff: Duration) -> Self {
+        self.retry_policy(RetryPolicy::new(max_attempts, backoff))
+    }
+
+    /// Retries failed loads according to `policy`
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.hooks.retry = Some(policy);
+        self
+    }
+
+    /// Reloads entries in the background once `threshold` of their TTL has elapsed
+    ///
+    /// With a threshold of `0.8`, a hit on an entry that has used up 80% of its
+    /// TTL returns the current value immediately and starts a reload, so hot
+    /// keys are replaced before they expire and never miss. At most one refresh
+    /// per key runs at a time. A refresh calls the loader once, without retries
+    /// or a timeout, and a failed refresh keeps the current value until it
+    /// expires. Thresholds above `1.0` are treated as `1.0`, and ones that
+    /// aren't positive, including NaN, leave refresh-ahead off.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub fn refresh_ahead(mut self, threshold: f64) -> Self {
+        self.options.refresh_ahead = Some(threshold);
+        self
+    }
+
+    /// Lets at most `limit` loader calls run at once, across all keys
+    ///
+    /// Misses beyond the limit wait for a running load to finish, which gives
+    /// a fragile backend backpressure. Hits never wait. Background refreshes
+    /// count toward the limit too. A limit of zero is treated as one.
+    pub fn max_concurrent_loads(mut self, limit: usize) -> Self {
+        self.options.max_concurrent_loads = Some(limit);
+        self
+    }
+
+    /// Chooses whether a failed reload of an expired entry serves the stale value
+    ///
+    /// Defaults to [`ReloadErrorPolicy::PropagateError`]. With
+    /// [`ReloadErrorPolicy::StaleFallback`], the stale value is returned instead
+    /// of the error and isn't negative cached, as long as it hasn't been purged.
+    pub fn on_reload_error(mut self, policy: ReloadErrorPolicy) -> Self {
+        self.options.on_reload_error = policy;
+        self
+    }
+
+    /// Stops calling the loader for a while once it keeps failing
+    ///
+    /// See [`CircuitBreaker`] for when the circuit opens and closes. It guards
+    /// every loader call: the loader passed to the builder, background
+    /// refreshes, the batch loader and loaders passed to
+    /// [`Cache::get_or_load_with`].
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.options.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Prefixes every identifier with `namespace:`
+    ///
+    /// Snapshots, dumps and events carry the prefixed identifiers, and
+    /// [`Cache::invalidate_namespace`] removes everything under the prefix.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.options.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Keeps identifiers in sorted order alongside the hash map
+    ///
+    /// [`Cache::range`] and [`Cache::remove_prefix`] then take O(log n + k)
+    /// per shard instead of scanning every entry, which suits hierarchical
+    /// identifiers like `user:123:profile`. Point lookups are unaffected, but
+    /// inserting a new identifier or removing one also updates the index.
+    pub fn ordered_index(mut self) -> Self {
+        self.options.ordered_index = true;
+        self
+    }
+
+    /// Checks expiry on reads against a clock refreshed once per `granularity`
+    ///
+    /// Hits read an atomic instead of the system clock, which helps read-heavy
+    /// workloads. Entries may then be served for up to one granularity past
+    /// their expiry. The clock is updated by a background thread that exits
+    /// once the cache is dropped.
+    pub fn clock_granularity(mut self, granularity: Duration) -> Self {
+        self.options.clock_granularity = Some(granularity);
+        self
+    }
+
+    /// Treats entries as live until `tolerance` past their expiry
+    ///
+    /// Absorbs clock jitter on platforms with a coarse `SystemTime`, so very
+    /// short TTLs don't expire early when the clock reading and the expiry
+    /// round to the same instant. Soft expiry gets the same margin, and so do
+    /// purging, eviction and in-place updates, so an entry a read would serve
+    /// is never treated as expired elsewhere. Defaults to zero.
+    pub fn expiry_tolerance(mut self, tolerance: Duration) -> Self {
+        self.options.expiry_tolerance = tolerance;
+        self
+    }
+
+    /// Chooses what happens to a shard whose lock was poisoned by a panic
+    ///
+    /// Defaults to [`PoisonPolicy::Ignore`].
+    pub fn poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.options.poison_policy = policy;
+        self
+    }
+
+    /// Sets how many events each subscriber can fall behind before lagging
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.options.event_capacity = capacity;
+        self
+    }
+
+    /// Sets the number of storage shards
+    pub fn shards(mut self, shard_count: usize) -> Self {
+        self.options.shard_count = shard_count;
+        self
+    }
+}
+
+impl<K, V, F, G, S> CacheBuilder<K, V, F, G, S>
+where
+    K: Clone + Send + Sync,
+    V: Clone + Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<V>,
+    G: KeyMapper<K> + Send + Sync,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Builds the cache
+    pub fn build(self) -> Cache<K, V, F, G, S> {
+        Cache::from_parts(
+            self.load,
+            self.get_key_for_map,
+            self.options,
+            self.hooks,
+            self.hasher,
+        )
+    }
+}
@@ -1,159 +1,2605 @@
-use std::collections::HashMap;
+use crate::backing::BackingStore;
+use crate::breaker::CircuitBreaker;
+use crate::entry::EntryHandle;
+use crate::error::CacheError;
+use crate::events::{CacheEvent, CacheOutcome, EvictionReason, ValueSource};
+use crate::flight::{Flight, InFlight, LoadGuard};
+use crate::join::{join_all, try_join_all};
+use crate::key::{CollisionCheck, KeyCodec, KeyMapper, TryKeyMapper};
+use crate::snapshot::{CacheSnapshot, SnapshotEntry};
+use crate::stats::{CacheStats, StatsCounters};
+use crate::store::{Store, StoredKey};
+use crate::unwind::catch_loader_panic;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use crate::{retry::RetryPolicy, runtime};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::error::Error;
+use std::fmt;
 use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::ops::RangeBounds;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
-use std::time::SystemTime;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::broadcast;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use tokio::sync::oneshot;
+
+/// Number of shards used by [`Cache::new`]
+pub const DEFAULT_SHARD_COUNT: usize = 16;
+
+/// Number of events buffered for each subscriber before it starts lagging
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// How recently a reloaded entry must have expired to count as a near miss
+const NEAR_MISS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Error type returned by loaders
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Future returned by a cache loader
+pub type LoadFuture<V> = Pin<Box<dyn Future<Output = Result<Expiring<V>, BoxError>> + Send>>;
+
+/// Future returned by a loader whose keys may have no value, see [`Cache::optional`]
+pub type OptionalLoadFuture<V> =
+    Pin<Box<dyn Future<Output = Result<Option<Expiring<V>>, BoxError>> + Send>>;
+
+/// Future returned by a loader that also produces metadata, see [`Cache::with_meta`]
+pub type MetaLoadFuture<V, M> =
+    Pin<Box<dyn Future<Output = Result<(Expiring<V>, M), BoxError>> + Send>>;
+
+/// Future returned by a batch loader, see [`Cache::with_batch_loader`]
+pub type BatchLoadFuture<K, V> =
+    Pin<Box<dyn Future<Output = Result<Vec<(K, Expiring<V>)>, BoxError>> + Send>>;
+
+/// Loader fetching several keys in one call, see [`Cache::get_batch`]
+pub type BatchLoader<K, V> = Box<dyn Fn(Vec<K>) -> BatchLoadFuture<K, V> + Send + Sync>;
 
 /// Represents a value with an expiration time
+///
+/// An `expires_at` of `None` means the value never expires. A value can also
+/// carry a soft expiry, see [`with_soft_ttl`](Self::with_soft_ttl): past it
+/// the value is stale but still served, and a hit reloads it in the background, while past `expires_at` a `get`
+/// waits for a reload.
+///
+/// With the `serde` feature enabled this implements `Serialize` and
+/// `Deserialize`, with times encoded as seconds and nanoseconds since the
+/// Unix epoch, so entries can be shared with other processes.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expiring<T> {
-    pub expires_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+    /// Defaults to `None` when reading entries saved without it
+    #[cfg_attr(feature = "serde", serde(default))]
+    soft_expires_at: Option<SystemTime>,
     pub value: T,
 }
 
 impl<T> Expiring<T> {
     /// Creates a new expiring value
     pub fn new(value: T, expires_at: SystemTime) -> Self {
-        Self { expires_at, value }
+        Self {
+            expires_at: Some(expires_at),
+            soft_expires_at: None,
+            value,
+        }
     }
 
     /// Creates a new expiring value that expires after the given duration
-    pub fn with_duration(value: T, duration: std::time::Duration) -> Self {
+    pub fn with_duration(value: T, duration: Duration) -> Self {
         let expires_at = SystemTime::now() + duration;
         Self::new(value, expires_at)
     }
 
+    /// Creates a value that never expires
+    pub fn never(value: T) -> Self {
+        Self {
+            expires_at: None,
+            soft_expires_at: None,
+            value,
+        }
+    }
+
+    /// Creates a value that goes stale after `soft` and expires after `hard`
+    ///
+    /// Between the two, hits are served the stale value and trigger a
+    /// background reload, which needs the `tokio` or `async-std` feature.
+    /// After `hard`, a `get` reloads it before returning.
+    pub fn with_soft_ttl(value: T, soft: Duration, hard: Duration) -> Self {
+        let now = SystemTime::now();
+        Self {
+            expires_at: Some(now + hard),
+            soft_expires_at: Some(now + soft.min(hard)),
+            value,
+        }
+    }
+
+    /// Creates a value that goes stale at `soft_expires_at` and expires at `expires_at`
+    ///
+    /// The absolute-time counterpart of [`with_soft_ttl`](Self::with_soft_ttl),
+    /// e.g. for rebuilding a saved entry. A soft expiry later than the hard one
+    /// is moved back to it.
+    pub fn with_soft_expiry(
+        value: T,
+        soft_expires_at: SystemTime,
+        expires_at: Option<SystemTime>,
+    ) -> Self {
+        Self {
+            expires_at,
+            soft_expires_at: Some(expires_at.map_or(soft_expires_at, |expires_at| {
+                soft_expires_at.min(expires_at)
+            })),
+            value,
+        }
+    }
+
+    /// Creates a value for a loader to return without it being cached
+    ///
+    /// The value is already expired, so `get` hands it to the caller but the
+    /// next `get` calls the loader again. Useful for degraded results that
+    /// shouldn't outlive the request that produced them.
+    pub fn no_store(value: T) -> Self {
+        Self::new(value, SystemTime::UNIX_EPOCH)
+    }
+
     /// Checks if this item has expired
     pub fn is_expired(&self) -> bool {
-        SystemTime::now() > self.expires_at
+        self.is_expired_at(SystemTime::now())
+    }
+
+    /// Checks if this item has expired as of `now`
+    ///
+    /// An item is still live at the exact instant of its expiry and expired
+    /// from the next moment on. Handy for testing expiry without sleeping.
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| now > expires_at)
+    }
+
+    /// Returns when this item goes stale, or `None` if it has no soft expiry
+    pub fn soft_expires_at(&self) -> Option<SystemTime> {
+        self.soft_expires_at
+    }
+
+    /// Checks if this item is past its soft expiry, whether or not it has expired
+    pub fn is_stale(&self) -> bool {
+        self.is_stale_at(SystemTime::now())
+    }
+
+    /// Checks if this item is past its soft expiry as of `now`
+    pub fn is_stale_at(&self, now: SystemTime) -> bool {
+        self.soft_expires_at
+            .is_some_and(|soft_expires_at| now > soft_expires_at)
+    }
+
+    /// Returns the time left until this item expires, or zero if it already has
+    ///
+    /// Values that never expire report `Duration::MAX`.
+    pub fn remaining_ttl(&self) -> Duration {
+        self.time_to_live().unwrap_or(Duration::ZERO)
+    }
+
+    /// Returns the time left until this item expires, or `None` if it already has
+    ///
+    /// Values that never expire report `Some(Duration::MAX)`.
+    pub fn time_to_live(&self) -> Option<Duration> {
+        match self.expires_at {
+            Some(expires_at) => expires_at.duration_since(SystemTime::now()).ok(),
+            None => Some(Duration::MAX),
+        }
+    }
+
+    /// Transforms the value, keeping the same expiry
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Expiring<U> {
+        Expiring {
+            expires_at: self.expires_at,
+            soft_expires_at: self.soft_expires_at,
+            value: f(self.value),
+        }
+    }
+
+    /// Borrows the value, keeping the same expiry
+    pub fn as_ref(&self) -> Expiring<&T> {
+        Expiring {
+            expires_at: self.expires_at,
+            soft_expires_at: self.soft_expires_at,
+            value: &self.value,
+        }
+    }
+}
+
+/// An [`Expiring`] ordered by its expiry alone, soonest first
+///
+/// `T` doesn't need to be `Ord`, and values that never expire sort after every
+/// value that does. Wrap it in [`Reverse`](std::cmp::Reverse) to pop the
+/// soonest-expiring item first from a `BinaryHeap`, which is how each shard
+/// queues its entries so [`Cache::purge_expired`] only visits expired ones.
+#[derive(Debug, Clone)]
+pub struct ByExpiry<T>(pub Expiring<T>);
+
+impl<T> ByExpiry<T> {
+    /// Unwraps the item
+    pub fn into_inner(self) -> Expiring<T> {
+        self.0
+    }
+
+    fn sort_key(&self) -> (bool, Option<SystemTime>) {
+        (self.0.expires_at.is_none(), self.0.expires_at)
+    }
+}
+
+impl<T> From<Expiring<T>> for ByExpiry<T> {
+    fn from(item: Expiring<T>) -> Self {
+        Self(item)
+    }
+}
+
+impl<T> PartialEq for ByExpiry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expires_at == other.0.expires_at
+    }
+}
+
+impl<T> Eq for ByExpiry<T> {}
+
+impl<T> PartialOrd for ByExpiry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for ByExpiry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+/// How entries are chosen for eviction when the cache is over capacity
+///
+/// Expired entries are always evicted first, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EvictionPolicy {
+    /// Evict the least recently used entry
+    #[default]
+    Lru,
+    /// Evict the least frequently used entry, breaking ties by recency
+    ///
+    /// Frequencies are halved after every ten hits per unit of capacity, so
+    /// keys that were popular a long time ago eventually age out.
+    Lfu,
+}
+
+/// What `get` does when reloading an expired entry fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReloadErrorPolicy {
+    /// Return the loader's error
+    #[default]
+    PropagateError,
+    /// Serve the expired value if it's still stored, and keep it for `grace`
+    ///
+    /// During the grace period reads are hits, so a struggling backend isn't
+    /// retried on every `get`. A zero grace serves the stale value but tries
+    /// the loader again on the next `get`.
+    StaleFallback { grace: Duration },
+}
+
+/// What the cache does with a shard whose lock was poisoned by a panic
+///
+/// A panic while a shard's lock is held, such as in an
+/// [`update`](Cache::update) closure or a weigher, poisons that shard. The
+/// other shards aren't affected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Treat the shard as empty and drop writes to it
+    ///
+    /// Every `get` for a key in the shard is a miss: the value is loaded and
+    /// returned, but not cached.
+    #[default]
+    Ignore,
+    /// Make methods that return a `Result` fail with [`CacheError::LockPoisoned`]
+    ///
+    /// Methods that can't report an error, such as `size`, `insert` or
+    /// `delete`, behave as under [`Ignore`](Self::Ignore).
+    Error,
+    /// Clear the poison and keep using the shard's data as the panic left it
+    ///
+    /// The data may be inconsistent, for example a half-applied update, but
+    /// the cache keeps serving it.
+    Recover,
+}
+
+/// Tunable settings for a [`Cache`]
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    /// Number of shards the storage is split into
+    pub shard_count: usize,
+    /// Number of entries to allocate room for up front, spread across the shards
+    pub initial_capacity: usize,
+    /// Maximum number of entries before the least recently used is evicted
+    pub max_capacity: Option<usize>,
+    /// Maximum total weight, as measured by the weigher, before entries are evicted
+    pub max_weight: Option<usize>,
+    /// Which entries are evicted first when over capacity
+    pub eviction_policy: EvictionPolicy,
+    /// How many entries to evict at once when over `max_capacity`
+    pub eviction_batch: usize,
+    /// TTL applied to values inserted with [`Cache::put`]
+    pub default_ttl: Option<Duration>,
+    /// On every hit, pushes the entry's expiry out to at least this far from now
+    pub sliding: Option<Duration>,
+    /// Hard limit on how long an entry lives after it's stored, whatever its expiry
+    pub max_age: Option<Duration>,
+    /// How long loader errors are cached for
+    pub negative_ttl: Option<Duration>,
+    /// How long a key isn't loaded again after its load fails
+    pub failure_cooldown: Option<Duration>,
+    /// Number of events buffered for each subscriber
+    pub event_capacity: usize,
+    /// Longest a single loader call may run before `get` gives up on it
+    pub load_timeout: Option<Duration>,
+    /// Upper bound of the random offset added to the expiry of loaded values
+    pub jitter: Option<Duration>,
+    /// Seed for the jitter's random offsets, random per cache when unset
+    pub jitter_seed: Option<u64>,
+    /// Fraction of an entry's TTL after which a hit reloads it in the background, clamped to `(0.0, 1.0]`
+    pub refresh_ahead: Option<f64>,
+    /// Most loader calls allowed to run at once across all keys
+    pub max_concurrent_loads: Option<usize>,
+    /// Whether a failed reload of an expired entry serves the stale value
+    pub on_reload_error: ReloadErrorPolicy,
+    /// Stops calling the loader for a while after repeated failures
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Prefix added to every identifier as `namespace:identifier`
+    pub namespace: Option<String>,
+    /// Keeps identifiers sorted too, so range and prefix queries don't scan every entry
+    pub ordered_index: bool,
+    /// How often the cached clock used for expiry checks on reads is refreshed
+    pub clock_granularity: Option<Duration>,
+    /// How long past its expiry an entry is still served on reads
+    pub expiry_tolerance: Duration,
+    /// What happens to a shard whose lock was poisoned by a panic
+    pub poison_policy: PoisonPolicy,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            shard_count: DEFAULT_SHARD_COUNT,
+            initial_capacity: 0,
+            max_capacity: None,
+            max_weight: None,
+            eviction_policy: EvictionPolicy::Lru,
+            eviction_batch: 1,
+            default_ttl: None,
+            sliding: None,
+            max_age: None,
+            negative_ttl: None,
+            failure_cooldown: None,
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            load_timeout: None,
+            jitter: None,
+            jitter_seed: None,
+            refresh_ahead: None,
+            max_concurrent_loads: None,
+            on_reload_error: ReloadErrorPolicy::PropagateError,
+            circuit_breaker: None,
+            namespace: None,
+            ordered_index: false,
+            clock_granularity: None,
+            expiry_tolerance: Duration::ZERO,
+            poison_policy: PoisonPolicy::Ignore,
+        }
+    }
+}
+
+/// Callback invoked with the identifier, value and reason when an entry leaves the cache
+pub type EvictionCallback<V> = Arc<dyn Fn(&str, &V, EvictionReason) + Send + Sync>;
+
+/// Function measuring the weight of a value for [`CacheBuilder::with_weigher`](crate::CacheBuilder::with_weigher)
+pub type Weigher<V> = Arc<dyn Fn(&V) -> usize + Send + Sync>;
+
+/// Function applied to every loaded value, see [`CacheBuilder::transform`](crate::CacheBuilder::transform)
+pub type Transform<V> = Arc<dyn Fn(V) -> V + Send + Sync>;
+
+/// Function turning a key's access count and loaded TTL into the TTL to cache it
+/// with, see [`CacheBuilder::adaptive_ttl`](crate::CacheBuilder::adaptive_ttl)
+pub type AdaptiveTtl = Arc<dyn Fn(u64, Duration) -> Duration + Send + Sync>;
+
+/// User-supplied callbacks, kept apart from the plain-data [`CacheOptions`]
+pub(crate) struct Hooks<V> {
+    pub(crate) on_evict: Option<EvictionCallback<V>>,
+    pub(crate) weigher: Option<Weigher<V>>,
+    pub(crate) transform: Option<Transform<V>>,
+    pub(crate) adaptive_ttl: Option<AdaptiveTtl>,
+    pub(crate) backing: Option<Arc<dyn BackingStore<V>>>,
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) retry: Option<RetryPolicy>,
+}
+
+impl<V> Default for Hooks<V> {
+    fn default() -> Self {
+        Self {
+            on_evict: None,
+            weigher: None,
+            transform: None,
+            adaptive_ttl: None,
+            backing: None,
+            #[cfg(any(feature = "tokio", feature = "async-std"))]
+            retry: None,
+        }
+    }
+}
+
+/// Live entries formatted as a map, see [`Cache::debug_entries`]
+struct DebugEntries<V>(Vec<(String, Expiring<V>)>);
+
+impl<V: fmt::Debug> fmt::Debug for DebugEntries<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(identifier, item)| (identifier, item)))
+            .finish()
     }
 }
 
 /// Configuration for the Cache
+///
+/// [`Cache::get_config`] borrows the loader and key mapper, while
+/// [`Cache::to_config`] clones them into an owned config. Either can be passed
+/// to [`Cache::from_config`] to build another cache that loads the same way.
+/// Eviction callbacks, the weigher, the retry policy and the hasher aren't
+/// part of the config.
 #[derive(Clone)]
 pub struct CacheConfig<K, V, F, G> {
     pub load: F,
     pub get_key_for_map: G,
+    pub options: CacheOptions,
     _phantom: std::marker::PhantomData<(K, V)>,
 }
 
+impl<K, V, F, G> CacheConfig<K, V, F, G> {
+    /// Creates a config from a loader, a key mapper and options
+    pub fn new(load: F, get_key_for_map: G, options: CacheOptions) -> Self {
+        Self {
+            load,
+            get_key_for_map,
+            options,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
 /// A generic cache with expiration support
-pub struct Cache<K, V, F, G>
+///
+/// Entries are spread across a fixed number of shards, each guarded by its own
+/// `RwLock`, so writes to unrelated keys don't serialize on a single lock.
+///
+/// `S` hashes identifiers both to pick a shard and inside each shard's map. It
+/// defaults to the standard library's DoS-resistant SipHash; see
+/// [`with_hasher`](Cache::with_hasher) to plug in a faster one for trusted keys.
+pub struct Cache<K, V, F, G, S = RandomState>
 where
     K: Clone,
     V: Clone,
-    F: Fn(
-        K,
-    ) -> Pin<
-        Box<
-            dyn Future<Output = Result<Expiring<V>, Box<dyn std::error::Error + Send + Sync>>>
-                + Send,
-        >,
-    >,
-    G: Fn(&K) -> String,
+    F: Fn(K) -> LoadFuture<V>,
+    G: KeyMapper<K>,
 {
-    map: std::sync::RwLock<HashMap<String, Expiring<V>>>,
+    store: Arc<Store<V, S>>,
+    negative: Arc<RwLock<HashMap<String, Expiring<CacheError>>>>,
+    in_flight: Arc<InFlight<(Expiring<V>, ValueSource)>>,
+    stats: Arc<StatsCounters>,
     load: F,
+    batch_load: Option<Arc<BatchLoader<K, V>>>,
     get_key_for_map: G,
+    collisions: Option<Arc<CollisionCheck<K>>>,
+    key_codec: Option<KeyCodec<K>>,
     _phantom: std::marker::PhantomData<K>,
 }
 
 impl<K, V, F, G> Cache<K, V, F, G>
 where
     K: Clone + Send + Sync,
-    V: Clone + Send + Sync,
-    F: Fn(
-        K,
-    ) -> Pin<
-        Box<
-            dyn Future<Output = Result<Expiring<V>, Box<dyn std::error::Error + Send + Sync>>>
-                + Send,
-        >,
-    >,
+    V: Clone + Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<V>,
     G: Fn(&K) -> String + Send + Sync,
 {
     /// Creates a new cache with the given loader and key mapper functions
     pub fn new(load: F, get_key_for_map: G) -> Self {
+        Self::from_parts(
+            load,
+            get_key_for_map,
+            CacheOptions::default(),
+            Hooks::default(),
+            RandomState::new(),
+        )
+    }
+
+    /// Creates a new cache whose storage is split into `shard_count` shards
+    ///
+    /// A shard count of zero is treated as one.
+    pub fn with_shards(load: F, get_key_for_map: G, shard_count: usize) -> Self {
+        let options = CacheOptions {
+            shard_count,
+            ..CacheOptions::default()
+        };
+        Self::from_parts(
+            load,
+            get_key_for_map,
+            options,
+            Hooks::default(),
+            RandomState::new(),
+        )
+    }
+
+    /// Creates a new cache with room for `initial` entries allocated up front
+    ///
+    /// Saves rehashing while a cache known to grow large fills up. This only
+    /// sizes the storage; it doesn't limit how many entries are kept, see
+    /// [`CacheBuilder::max_capacity`](crate::CacheBuilder::max_capacity) for that.
+    pub fn with_initial_capacity(load: F, get_key_for_map: G, initial: usize) -> Self {
+        let options = CacheOptions {
+            initial_capacity: initial,
+            ..CacheOptions::default()
+        };
+        Self::from_parts(
+            load,
+            get_key_for_map,
+            options,
+            Hooks::default(),
+            RandomState::new(),
+        )
+    }
+
+    /// Creates a new, empty cache from a config
+    ///
+    /// The new cache shares nothing with the one the config came from except
+    /// its loading and key mapping logic and its options, which makes it a
+    /// handy base for per-tenant cache factories.
+    pub fn from_config(config: CacheConfig<K, V, F, G>) -> Self {
+        Self::from_parts(
+            config.load,
+            config.get_key_for_map,
+            config.options,
+            Hooks::default(),
+            RandomState::new(),
+        )
+    }
+}
+
+impl<K, V, F, M> Cache<K, V, F, TryKeyMapper<M>>
+where
+    K: Clone + Send + Sync,
+    V: Clone + Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<V>,
+    TryKeyMapper<M>: KeyMapper<K> + Send + Sync,
+{
+    /// Creates a cache whose key mapper can reject keys, see [`TryKeyMapper`]
+    ///
+    /// `get` and the other lookups return [`CacheError::InvalidKey`] for a
+    /// rejected key without calling the loader.
+    pub fn with_key_validation<E>(load: F, get_key_for_map: M) -> Self
+    where
+        M: Fn(&K) -> Result<String, E>,
+        E: Into<BoxError>,
+    {
+        Self::from_parts(
+            load,
+            TryKeyMapper(get_key_for_map),
+            CacheOptions::default(),
+            Hooks::default(),
+            RandomState::new(),
+        )
+    }
+}
+
+impl<K, V, G> Cache<K, V, fn(K) -> LoadFuture<V>, G>
+where
+    K: Clone + Send + Sync,
+    V: Clone + Send + Sync + 'static,
+    G: Fn(&K) -> String + Send + Sync,
+{
+    /// Creates a cache whose loader borrows a shared context on every load
+    ///
+    /// The cache owns `ctx` and passes it by reference, so a DB pool or HTTP
+    /// client can be injected once instead of being cloned into the loader
+    /// closure. The loader's future must still be `'static`, so anything it
+    /// needs across an `.await` has to be taken out of the context first.
+    ///
+    /// ```rust
+    /// use cache_rs::{Cache, Expiring};
+    ///
+    /// struct Client {
+    ///     prefix: String,
+    /// }
+    ///
+    /// let cache = Cache::with_context(
+    ///     Client { prefix: "user".to_string() },
+    ///     |key: u32, client: &Client| {
+    ///         let value = format!("{}_{}", client.prefix, key);
+    ///         Box::pin(async move { Ok(Expiring::never(value)) })
+    ///     },
+    ///     |key: &u32| key.to_string(),
+    /// );
+    /// # let _ = cache;
+    /// ```
+    pub fn with_context<C, L>(
+        ctx: C,
+        load: L,
+        get_key_for_map: G,
+    ) -> Cache<K, V, impl Fn(K) -> LoadFuture<V>, G>
+    where
+        L: Fn(K, &C) -> LoadFuture<V>,
+    {
+        Cache::new(move |key| load(key, &ctx), get_key_for_map)
+    }
+
+    /// Creates a cache for keys that may legitimately have no value
+    ///
+    /// The loader returns `Ok(None)` when the key doesn't exist. That absence
+    /// is cached for `absent_ttl`, or not at all if it's `None`, and reads
+    /// back as `Ok(None)` from [`get_optional`](Cache::get_optional). Cached
+    /// absences count toward [`size`](Cache::size) like any other entry.
+    pub fn optional<L>(
+        load: L,
+        get_key_for_map: G,
+        absent_ttl: Option<Duration>,
+    ) -> Cache<K, Option<V>, impl Fn(K) -> LoadFuture<Option<V>>, G>
+    where
+        L: Fn(K) -> OptionalLoadFuture<V>,
+    {
+        let absent_ttl = absent_ttl.unwrap_or(Duration::ZERO);
+        Cache::new(
+            move |key| {
+                let load = load(key);
+                Box::pin(async move {
+                    Ok(match load.await? {
+                        Some(item) => item.map(Some),
+                        // A zero TTL is already expired, so the absence isn't cached
+                        None => Expiring::with_duration(None, absent_ttl),
+                    })
+                }) as LoadFuture<Option<V>>
+            },
+            get_key_for_map,
+        )
+    }
+
+    /// Creates a cache that keeps metadata, such as an ETag, alongside each value
+    ///
+    /// The loader returns the value with its expiry and the metadata separately,
+    /// so metadata never affects when an entry expires. Read both back with
+    /// [`get_with_meta`](Cache::get_with_meta).
+    #[allow(clippy::type_complexity)]
+    pub fn with_meta<M, L>(
+        load: L,
+        get_key_for_map: G,
+    ) -> Cache<K, (V, M), impl Fn(K) -> LoadFuture<(V, M)>, G>
+    where
+        M: Clone + Send + Sync + 'static,
+        L: Fn(K) -> MetaLoadFuture<V, M>,
+    {
+        Cache::new(
+            move |key| {
+                let load = load(key);
+                Box::pin(async move {
+                    let (item, meta) = load.await?;
+                    Ok(item.map(|value| (value, meta)))
+                }) as LoadFuture<(V, M)>
+            },
+            get_key_for_map,
+        )
+    }
+}
+
+impl<K, T, G> Cache<K, Arc<T>, fn(K) -> LoadFuture<Arc<T>>, G>
+where
+    K: Clone + Send + Sync,
+    T: Send + Sync + 'static,
+    G: Fn(&K) -> String + Send + Sync,
+{
+    /// Creates a cache for values that can't or shouldn't be cloned
+    ///
+    /// The loader returns plain values, which the cache wraps in an `Arc` on
+    /// insert, so `T` doesn't need to be `Clone`. Every read hands out a clone
+    /// of the `Arc`, see [`get_arc`](Cache::get_arc).
+    pub fn shared<L>(
+        load: L,
+        get_key_for_map: G,
+    ) -> Cache<K, Arc<T>, impl Fn(K) -> LoadFuture<Arc<T>>, G>
+    where
+        L: Fn(K) -> LoadFuture<T>,
+    {
+        Cache::new(
+            move |key| {
+                let load = load(key);
+                Box::pin(async move { Ok(load.await?.map(Arc::new)) }) as LoadFuture<Arc<T>>
+            },
+            get_key_for_map,
+        )
+    }
+}
+
+/// Clones are handles to the same cache
+///
+/// Entries, running loads, cached failures and statistics are shared, so a
+/// value inserted or loaded through one handle is a hit through every other.
+/// Only the loader and key mapper are cloned. Cloning is cheap, which makes
+/// this an alternative to wrapping the cache in an `Arc` to share it between
+/// tasks.
+impl<K, V, F, G, S> Clone for Cache<K, V, F, G, S>
+where
+    K: Clone,
+    V: Clone,
+    F: Fn(K) -> LoadFuture<V> + Clone,
+    G: KeyMapper<K> + Clone,
+{
+    fn clone(&self) -> Self {
         Self {
-            map: std::sync::RwLock::new(HashMap::new()),
+            store: self.store.clone(),
+            negative: self.negative.clone(),
+            in_flight: self.in_flight.clone(),
+            stats: self.stats.clone(),
+            load: self.load.clone(),
+            batch_load: self.batch_load.clone(),
+            get_key_for_map: self.get_key_for_map.clone(),
+            collisions: self.collisions.clone(),
+            key_codec: self.key_codec,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<K, V, F, G, S> fmt::Debug for Cache<K, V, F, G, S>
+where
+    K: Clone,
+    V: Clone,
+    F: Fn(K) -> LoadFuture<V>,
+    G: KeyMapper<K>,
+    S: BuildHasher + Clone,
+{
+    /// Summarizes the cache without needing `V: Debug`; see [`Cache::debug_entries`] for the contents
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("size", &self.store.size())
+            .field("max_capacity", &self.store.options.max_capacity)
+            .field("shard_count", &self.store.shard_count())
+            .field("load", &format_args!("<loader>"))
+            .field("get_key_for_map", &format_args!("<key_mapper>"))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, M, F, G, S> Cache<K, (V, M), F, G, S>
+where
+    K: Clone + Send + Sync,
+    V: Clone + Send + Sync + 'static,
+    M: Clone + Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<(V, M)>,
+    G: KeyMapper<K> + Send + Sync,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Gets a value together with its metadata, see [`Cache::with_meta`]
+    pub async fn get_with_meta(&self, key: K) -> Result<(V, M), CacheError> {
+        self.get(key).await
+    }
+
+    /// Inserts a value with its metadata, replacing any existing entry for the key
+    ///
+    /// The entry expires according to `item` alone.
+    pub fn insert_with_meta(&self, key: K, item: Expiring<V>, meta: M) {
+        self.insert(key, item.map(|value| (value, meta)));
+    }
+}
+
+impl<K, T, F, G, S> Cache<K, Arc<T>, F, G, S>
+where
+    K: Clone + Send + Sync,
+    T: Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<Arc<T>>,
+    G: KeyMapper<K> + Send + Sync,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Gets a shared handle to a value
+    ///
+    /// The cache stores the `Arc`, so a hit only bumps its reference count
+    /// instead of copying the value.
+    pub async fn get_arc(&self, key: K) -> Result<Arc<T>, CacheError> {
+        self.get(key).await
+    }
+
+    /// Gets shared handles to several values, see [`get_many`](Cache::get_many)
+    ///
+    /// Hits point at the same allocations the cache holds, so fanning out over
+    /// large values costs a reference count bump each.
+    pub async fn get_many_arc(&self, keys: Vec<K>) -> Vec<Result<Arc<T>, CacheError>> {
+        self.get_many(keys).await
+    }
+}
+
+impl<K, V, F, G, S> Cache<K, Option<V>, F, G, S>
+where
+    K: Clone + Send + Sync,
+    V: Clone + Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<Option<V>>,
+    G: KeyMapper<K> + Send + Sync,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Gets a value that may be absent, see [`Cache::optional`]
+    ///
+    /// `Ok(None)` means the key has no value, whether that was just loaded or
+    /// is a cached absence.
+    pub async fn get_optional(&self, key: K) -> Result<Option<V>, CacheError> {
+        self.get(key).await
+    }
+}
+
+impl<K, V, F, G, S> Cache<K, V, F, G, S>
+where
+    K: Clone + Send + Sync,
+    V: Clone + Send + Sync + 'static,
+    F: Fn(K) -> LoadFuture<V>,
+    G: KeyMapper<K> + Send + Sync,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    /// Creates a new cache that hashes identifiers with `hasher`
+    pub fn with_hasher(load: F, get_key_for_map: G, hasher: S) -> Self {
+        Self::from_parts(
             load,
             get_key_for_map,
+            CacheOptions::default(),
+            Hooks::default(),
+            hasher,
+        )
+    }
+
+    pub(crate) fn from_parts(
+        load: F,
+        get_key_for_map: G,
+        options: CacheOptions,
+        hooks: Hooks<V>,
+        hasher: S,
+    ) -> Self {
+        Self {
+            store: Arc::new(Store::new(options, hooks, hasher)),
+            negative: Arc::new(RwLock::new(HashMap::new())),
+            in_flight: Arc::new(InFlight::new()),
+            stats: Arc::new(StatsCounters::default()),
+            load,
+            batch_load: None,
+            get_key_for_map,
+            collisions: None,
+            key_codec: None,
             _phantom: std::marker::PhantomData,
         }
     }
 
+    /// Lets [`get_batch`](Self::get_batch) load all of its misses with one call to `load_batch`
+    ///
+    /// The batch loader returns the values it found along with their keys, in
+    /// any order. Every other lookup still goes through the per-key loader.
+    pub fn with_batch_loader(
+        mut self,
+        load_batch: impl Fn(Vec<K>) -> BatchLoadFuture<K, V> + Send + Sync + 'static,
+    ) -> Self {
+        self.batch_load = Some(Arc::new(Box::new(load_batch)));
+        self
+    }
+
+    /// Checks that no two different keys are ever mapped to the same identifier
+    ///
+    /// The first key seen for each identifier is remembered, and any other key
+    /// mapping to it fails with [`CacheError::KeyCollision`] instead of
+    /// sharing its entry; methods that can't fail ignore it, like they do
+    /// invalid keys. That catches key mappers that lose information, such as
+    /// `format!("{}:{}", a, b)` over strings containing `:`. Remembered keys
+    /// are never forgotten and every lookup takes a lock, so this is meant for
+    /// debug builds and tests.
+    pub fn detect_key_collisions(mut self) -> Self
+    where
+        K: PartialEq,
+    {
+        self.collisions = Some(Arc::new(CollisionCheck::new()));
+        self
+    }
+
+    /// Remembers the key each identifier came from, enabling typed key enumeration
+    ///
+    /// Identifiers can't be mapped back to keys, so without this the cache
+    /// only knows its entries by identifier. With it, [`reload_all`](Self::reload_all),
+    /// [`keys_typed`](Self::keys_typed) and [`delete_where`](Self::delete_where)
+    /// work with the original keys. Each entry keeps the key it was last
+    /// stored under, so this costs a clone of every cached key plus a small
+    /// allocation, and the key is dropped along with its entry. Entries
+    /// restored from a snapshot have no key until they're stored again.
+    pub fn remember_keys(mut self) -> Self
+    where
+        K: 'static,
+    {
+        self.key_codec = Some(KeyCodec::new());
+        self
+    }
+
     /// Gets a value from the cache, loading it if necessary or expired
-    pub async fn get(&self, key: K) -> Result<V, Box<dyn std::error::Error + Send + Sync>> {
+    ///
+    /// If the loader returns a value that has already expired, such as one with
+    /// a zero TTL, it's returned to the caller but not cached.
+    pub async fn get(&self, key: K) -> Result<V, CacheError> {
         let expiring = self.get_with_expiry(key).await?;
         Ok(expiring.value)
     }
 
-    /// Gets the cache configuration
+    /// Gets a value only if a live entry is already cached, never calling the loader
+    ///
+    /// Returns [`CacheError::NotCached`] on a miss so hot paths can handle it
+    /// explicitly instead of loading transparently.
+    pub async fn get_cached_only(&self, key: K) -> Result<V, CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        self.store
+            .get_non_expired(&identifier)
+            .map(|(item, _)| item.value)
+            .ok_or(CacheError::NotCached)
+    }
+
+    /// Gets a cached value without loading and without ever blocking on a lock
+    ///
+    /// Returns `None` if the key is absent, if its entry has expired, or if its
+    /// shard is locked by a writer at that moment; callers that can't wait
+    /// should treat all three as a miss. A peek doesn't count as a use, so it
+    /// doesn't affect LRU order, sliding expiry or refresh-ahead.
+    pub fn try_peek(&self, key: &K) -> Option<V> {
+        let identifier = self.identifier(key).ok()?;
+        self.store.try_peek(&identifier).map(|item| item.value)
+    }
+
+    /// Returns how long the cached entry for `key` has left, without loading it
+    ///
+    /// `None` means the key is absent or expired. Entries that never expire
+    /// report `Duration::MAX`. Like [`try_peek`](Self::try_peek) this doesn't
+    /// count as a use of the entry.
+    pub fn ttl_remaining(&self, key: &K) -> Option<Duration> {
+        let identifier = self.identifier(key).ok()?;
+        self.store.time_to_live(&identifier)
+    }
+
+    /// Returns when the live entry for `key` was last read, or stored if it never was
+    ///
+    /// Every hit records the time with an atomic store, so reads stay under
+    /// the shard's read lock. With `clock_granularity` set the time comes from
+    /// the cached clock and is only that precise. `None` means the key is
+    /// absent or expired; like [`try_peek`](Self::try_peek) this doesn't count
+    /// as a use of the entry.
+    pub fn last_accessed(&self, key: &K) -> Option<SystemTime> {
+        let identifier = self.identifier(key).ok()?;
+        self.store.last_accessed(&identifier)
+    }
+
+    /// Gets a value, serving an expired entry instead of reloading if `accept` allows it
+    ///
+    /// When the entry for `key` has expired but is still stored, `accept` is
+    /// called with its value and age, the time since it was stored. If it
+    /// returns true the stale value is returned as a hit without calling the
+    /// loader; otherwise the value is loaded as with [`get`](Self::get). Live
+    /// entries are returned without consulting `accept`.
+    pub async fn get_accept(
+        &self,
+        key: K,
+        accept: impl Fn(&V, Duration) -> bool,
+    ) -> Result<V, CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        if let Some((item, _)) = self.get_hit(&key, &identifier) {
+            return Ok(item.value);
+        }
+        if let Some((item, age)) = self.store.peek_stored(&identifier)
+            && accept(&item.value, age)
+        {
+            self.stats.record_hit();
+            return Ok(item.value);
+        }
+        let (item, _, _) = self.lookup(Cow::Owned(key), identifier, None).await?;
+        Ok(item.value)
+    }
+
+    /// Gets a value from the cache, falling back to `fallback` if loading fails
+    ///
+    /// The fallback value is returned as-is and never cached, so the next call
+    /// tries the loader again.
+    pub async fn get_or_else(&self, key: K, fallback: impl FnOnce(&CacheError) -> V) -> V {
+        match self.get(key).await {
+            Ok(value) => value,
+            Err(err) => fallback(&err),
+        }
+    }
+
+    /// Gets the cache configuration, borrowing the loader and key mapper
     pub fn get_config(&self) -> CacheConfig<K, V, &F, &G> {
-        CacheConfig {
-            load: &self.load,
-            get_key_for_map: &self.get_key_for_map,
-            _phantom: std::marker::PhantomData,
+        CacheConfig::new(
+            &self.load,
+            &self.get_key_for_map,
+            self.store.options.clone(),
+        )
+    }
+
+    /// Gets an owned copy of the cache configuration
+    ///
+    /// Unlike [`get_config`](Self::get_config), the result doesn't borrow the
+    /// cache, so it can outlive it or be moved into another task.
+    pub fn to_config(&self) -> CacheConfig<K, V, F, G>
+    where
+        F: Clone,
+        G: Clone,
+    {
+        CacheConfig::new(
+            self.load.clone(),
+            self.get_key_for_map.clone(),
+            self.store.options.clone(),
+        )
+    }
+
+    /// Gets a projection of a value, such as one of its fields
+    ///
+    /// Loads and hits work like [`get`](Self::get), but on a hit `f` runs on
+    /// the cached value in place, so only its result is cloned out rather than
+    /// the whole value. `f` runs under the entry's shard lock, so keep it cheap.
+    pub async fn get_mapped<U>(&self, key: K, f: impl Fn(&V) -> U) -> Result<U, CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        if let Some((projected, _)) = self.get_hit_with(&key, &identifier, |item| f(&item.value)) {
+            return Ok(projected);
         }
+        let (item, _, _) = self.lookup(Cow::Owned(key), identifier, None).await?;
+        Ok(f(&item.value))
     }
 
     /// Gets a value with its expiration information
-    pub async fn get_with_expiry(
+    pub async fn get_with_expiry(&self, key: K) -> Result<Expiring<V>, CacheError> {
+        let (item, _) = self.get_with_outcome(Cow::Owned(key)).await?;
+        Ok(item)
+    }
+
+    /// Gets a value along with how fresh it is
+    ///
+    /// Tells a fresh load apart from a cached hit, a value found in the backing
+    /// store, a stale hit that's being reloaded in the background (soft expiry
+    /// or refresh-ahead), and an expired value served because its reload failed. Only the call whose
+    /// reload failed, or that waited on it, reports the fallback; later hits
+    /// during the grace period report `Cached`.
+    pub async fn get_with_source(&self, key: K) -> Result<(V, ValueSource), CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        let (item, _, source) = self.lookup(Cow::Owned(key), identifier, None).await?;
+        Ok((item.value, source))
+    }
+
+    /// Gets a value, waiting at most `wait` on a load another caller started
+    ///
+    /// If the key is already being loaded by another `get` and that load
+    /// doesn't finish within `wait`, this returns [`CacheError::WaitTimeout`]
+    /// while the load carries on for everyone else. A call that starts the
+    /// load itself waits for it like [`get`](Self::get); use `load_timeout`
+    /// to bound the loader.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn get_timeout(&self, key: K, wait: Duration) -> Result<V, CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        let (item, _, _) = self.lookup(Cow::Owned(key), identifier, Some(wait)).await?;
+        Ok(item.value)
+    }
+
+    /// Gets a value, giving up with [`CacheError::DeadlineExceeded`] once `deadline` passes
+    ///
+    /// Meant for callers that already carry an absolute deadline. The whole
+    /// lookup, including the loader and any wait on another caller's load, is
+    /// bounded by the time left until `deadline`. If it has already passed,
+    /// this fails right away without looking at the cache or calling the
+    /// loader. A load this call led is cancelled when the deadline passes, and
+    /// anyone waiting on it starts over.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn get_by_deadline(&self, key: K, deadline: Instant) -> Result<V, CacheError> {
+        let Some(left) = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())
+        else {
+            return Err(CacheError::DeadlineExceeded);
+        };
+        let identifier = self.checked_identifier(&key)?;
+        let (item, _, _) = runtime::timeout(left, self.lookup(Cow::Owned(key), identifier, None))
+            .await
+            .ok_or(CacheError::DeadlineExceeded)??;
+        Ok(item.value)
+    }
+
+    /// Gets a value by borrowing the key, cloning it only if the loader needs it
+    ///
+    /// On a hit the key is only used to compute its identifier, so nothing is
+    /// cloned. On a miss, or when refresh-ahead kicks in, the key is cloned to
+    /// hand an owned copy to the loader.
+    pub async fn get_ref(&self, key: &K) -> Result<V, CacheError> {
+        let (item, _) = self.get_with_outcome(Cow::Borrowed(key)).await?;
+        Ok(item.value)
+    }
+
+    /// Gets a value along with whether it was a hit or had to be loaded
+    ///
+    /// Meant for per-request tracing; on a miss the outcome carries how long
+    /// the loader took. A failure served from the negative cache is returned
+    /// as an error, like with [`get`](Self::get).
+    pub async fn get_instrumented(&self, key: K) -> Result<(V, CacheOutcome), CacheError> {
+        let (item, outcome) = self.get_with_outcome(Cow::Owned(key)).await?;
+        Ok((item.value, outcome))
+    }
+
+    /// Gets several values at once, loading the missing ones concurrently
+    ///
+    /// Results come back in the order of `keys`. Keys that map to the same
+    /// identifier are looked up once; if that lookup fails, each of their
+    /// positions gets a copy of the one [`CacheError`], with a loader error
+    /// wrapped in a [`SharedError`](crate::SharedError). A key the key mapper
+    /// rejects fails only its own position.
+    pub async fn get_many(&self, keys: Vec<K>) -> Vec<Result<V, CacheError>> {
+        let mut lookups: Vec<(K, String)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let slots: Vec<Result<usize, CacheError>> = keys
+            .into_iter()
+            .map(|key| {
+                let identifier = self.checked_identifier(&key)?;
+                Ok(*positions.entry(identifier.clone()).or_insert_with(|| {
+                    lookups.push((key, identifier));
+                    lookups.len() - 1
+                }))
+            })
+            .collect();
+
+        let mut uses = vec![0usize; lookups.len()];
+        for &slot in slots.iter().flatten() {
+            uses[slot] += 1;
+        }
+        let found = join_all(
+            lookups
+                .into_iter()
+                .map(|(key, identifier)| self.lookup(Cow::Owned(key), identifier, None)),
+        )
+        .await;
+        let mut found: Vec<Option<Result<V, CacheError>>> = found
+            .into_iter()
+            .zip(&uses)
+            .map(|(result, &uses)| {
+                Some(match result {
+                    Ok((item, _, _)) => Ok(item.value),
+                    Err(error) if uses > 1 => Err(error.into_shared()),
+                    Err(error) => Err(error),
+                })
+            })
+            .collect();
+
+        // Every position but the last one for an identifier gets a clone
+        slots
+            .into_iter()
+            .map(|slot| {
+                let slot = slot?;
+                uses[slot] -= 1;
+                if uses[slot] == 0 {
+                    return found[slot].take().expect("taken only by its last position");
+                }
+                match found[slot]
+                    .as_ref()
+                    .expect("taken only by its last position")
+                {
+                    Ok(value) => Ok(value.clone()),
+                    Err(error) => Err(error.clone_shared()),
+                }
+            })
+            .collect()
+    }
+
+    /// Gets several values as a map from identifier to result
+    ///
+    /// Loads run concurrently like with [`get_many`](Self::get_many). Keys that
+    /// map to the same identifier share one entry and one lookup. Keys the key
+    /// mapper rejects have no identifier, so they're left out of the map.
+    pub async fn get_map(&self, keys: Vec<K>) -> HashMap<String, Result<V, CacheError>> {
+        let mut lookups: HashMap<String, K> = HashMap::new();
+        for key in keys {
+            if let Ok(identifier) = self.identifier(&key) {
+                lookups.entry(identifier).or_insert(key);
+            }
+        }
+        join_all(lookups.into_iter().map(|(identifier, key)| async move {
+            let result = self.lookup(Cow::Owned(key), identifier.clone(), None).await;
+            (identifier, result.map(|(item, _, _)| item.value))
+        }))
+        .await
+        .into_iter()
+        .collect()
+    }
+
+    /// Gets several values, failing as a whole if any of them fails
+    ///
+    /// Loads run concurrently like with [`get_many`](Self::get_many). The first
+    /// failure is returned as [`CacheError::BatchFailed`], naming the
+    /// identifier that failed, and the loads still running are dropped. Values
+    /// that finished loading before that stay cached. A key the key mapper
+    /// rejects fails the call before anything is loaded.
+    pub async fn get_all(&self, keys: Vec<K>) -> Result<Vec<V>, CacheError> {
+        let mut lookups: Vec<(K, String)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let slots = keys
+            .into_iter()
+            .map(|key| {
+                let identifier = self.checked_identifier(&key)?;
+                Ok(*positions.entry(identifier.clone()).or_insert_with(|| {
+                    lookups.push((key, identifier));
+                    lookups.len() - 1
+                }))
+            })
+            .collect::<Result<Vec<usize>, CacheError>>()?;
+
+        let values = try_join_all(lookups.into_iter().map(|(key, identifier)| async move {
+            match self.lookup(Cow::Owned(key), identifier.clone(), None).await {
+                Ok((item, _, _)) => Ok(item.value),
+                Err(source) => Err(CacheError::BatchFailed {
+                    identifier,
+                    source: Box::new(source),
+                }),
+            }
+        }))
+        .await?;
+        Ok(slots.into_iter().map(|slot| values[slot].clone()).collect())
+    }
+
+    /// Gets several values, loading every miss with a single batch loader call
+    ///
+    /// Results come back in the order of `keys`. Keys already being loaded by
+    /// another caller are waited on instead of batched, misses are looked up
+    /// in the backing store first like [`get`](Self::get) does, and a key the
+    /// batch loader returns no value for fails with [`CacheError::Load`]. The
+    /// batch loader is called once, without retries, within the load timeout
+    /// and the limit on concurrent loads, and behind the circuit breaker.
+    /// Loader errors, including a failure of the whole batch, are wrapped in a
+    /// [`SharedError`](crate::SharedError). Without a batch loader, see
+    /// [`with_batch_loader`](Self::with_batch_loader),
+    /// this is the same as [`get_many`](Self::get_many).
+    pub async fn get_batch(&self, keys: Vec<K>) -> Vec<Result<V, CacheError>> {
+        let Some(batch_load) = &self.batch_load else {
+            return self.get_many(keys).await;
+        };
+        let identifiers: Vec<Result<String, CacheError>> = keys
+            .iter()
+            .map(|key| self.checked_identifier(key))
+            .collect();
+
+        let mut resolved: HashMap<String, Result<V, CacheError>> = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut leading = Vec::new();
+        let mut waiting = Vec::new();
+        for (key, identifier) in keys.into_iter().zip(&identifiers) {
+            let Ok(identifier) = identifier else {
+                continue;
+            };
+            if !seen.insert(identifier) {
+                continue;
+            }
+            if let Some((item, _)) = self.get_hit(&key, identifier) {
+                resolved.insert(identifier.clone(), Ok(item.value));
+                continue;
+            }
+            self.stats.record_miss();
+            if let Some(error) = self.get_negative(identifier) {
+                resolved.insert(identifier.clone(), Err(error));
+                continue;
+            }
+            if self.store.is_closed() {
+                resolved.insert(identifier.clone(), Err(CacheError::ShuttingDown));
+                continue;
+            }
+            match self.in_flight.join(identifier) {
+                Flight::Leader(guard) => {
+                    leading.push((key, identifier.clone(), guard));
+                }
+                Flight::Follower(waiter) => {
+                    self.stats.record_coalesced();
+                    waiting.push((key, identifier.clone(), waiter));
+                }
+            }
+        }
+
+        // Keys stored meanwhile or found in the backing store skip the batch
+        let started = self.store.next_tick();
+        let leads = join_all(
+            leading
+                .into_iter()
+                .map(|(key, identifier, guard)| async move {
+                    let stored_key = self.stored_key(&key);
+                    let lead = self
+                        .resolve_without_loader(&identifier, &stored_key, guard, started)
+                        .await;
+                    (key, identifier, stored_key, lead)
+                }),
+        )
+        .await;
+        let mut batch = Vec::new();
+        let mut leading = Vec::new();
+        for (key, identifier, stored_key, lead) in leads {
+            match lead {
+                Ok((item, _)) => {
+                    resolved.insert(identifier, Ok(item.value));
+                }
+                Err(guard) => {
+                    leading.push((identifier, stored_key, guard));
+                    batch.push(key);
+                }
+            }
+        }
+
+        // Load before waiting on anyone else, so overlapping batches can't deadlock
+        if !batch.is_empty() {
+            let mut loaded = HashMap::new();
+            let result = self
+                .through_breaker(self.run_load(catch_loader_panic(|| batch_load(batch))))
+                .await;
+            let failure = match result {
+                Ok(items) => {
+                    for (key, item) in items {
+                        if let Ok(identifier) = self.identifier(&key) {
+                            loaded.insert(identifier, item);
+                        }
+                    }
+                    None
+                }
+                Err(error) => Some(error.into_shared()),
+            };
+            let mut landed = Vec::new();
+            for (identifier, stored_key, guard) in leading {
+                let result = match (&failure, loaded.remove(&identifier)) {
+                    (Some(error), _) => Err(error.clone_shared()),
+                    (None, Some(item)) => Ok(item),
+                    (None, None) => Err(CacheError::Load(
+                        format!("batch loader returned no value for {}", identifier).into(),
+                    )),
+                };
+                landed.push((
+                    identifier.clone(),
+                    self.land_load(identifier, stored_key, guard, result, started),
+                ));
+            }
+            join_all(landed.iter().filter_map(|(identifier, landed)| {
+                let loaded = landed.as_ref().ok()?;
+                Some(self.write_back(identifier, loaded))
+            }))
+            .await;
+            for (identifier, landed) in landed {
+                resolved.insert(identifier, landed.map(|(item, _)| item.value));
+            }
+        }
+
+        for (key, identifier, waiter) in waiting {
+            let landed = match waiter.landed().await {
+                Some(landed) => landed.map(|(item, _)| item.value),
+                // The other caller's load was cancelled, so load the key alone
+                None => self
+                    .lookup(Cow::Owned(key), identifier.clone(), None)
+                    .await
+                    .map(|(item, _, _)| item.value),
+            };
+            resolved.insert(identifier, landed);
+        }
+
+        let resolved: HashMap<String, Result<V, CacheError>> = resolved
+            .into_iter()
+            .map(|(identifier, result)| (identifier, result.map_err(CacheError::into_shared)))
+            .collect();
+        identifiers
+            .into_iter()
+            .map(|identifier| match &resolved[&identifier?] {
+                Ok(value) => Ok(value.clone()),
+                Err(error) => Err(error.clone_shared()),
+            })
+            .collect()
+    }
+
+    /// Preloads `keys` so the first real requests are hits
+    ///
+    /// Loads run concurrently like [`get_many`](Self::get_many), within
+    /// `max_concurrent_loads` if set, and keys that already have a live entry
+    /// aren't reloaded. Returns one result per key, in order, so failed preloads
+    /// can be logged.
+    pub async fn warm(&self, keys: impl IntoIterator<Item = K>) -> Vec<Result<(), CacheError>> {
+        self.get_many(keys.into_iter().collect())
+            .await
+            .into_iter()
+            .map(|result| result.map(drop))
+            .collect()
+    }
+
+    /// Reloads `key` unconditionally, returning the previous value and the new one
+    ///
+    /// The previous value is whatever was stored for the key, even if it had
+    /// expired, and is taken under the same lock that installs the new value,
+    /// so the pair can be diffed without racing a concurrent write. The reload
+    /// is a load like any other: if one is already running for the key, this
+    /// waits for it instead of calling the loader, with the previous value
+    /// taken before waiting. A value written or a clear made while the loader
+    /// runs wins over its result, and the value that won is returned as the new
+    /// one. On failure the stored value is left alone and the error isn't
+    /// negatively cached.
+    pub async fn refresh_compare(&self, key: K) -> Result<(Option<V>, V), CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        if self.store.is_closed() {
+            return Err(CacheError::ShuttingDown);
+        }
+
+        let mut key = Some(key);
+        loop {
+            match self.in_flight.join(&identifier) {
+                Flight::Leader(guard) => {
+                    let key = key.take().expect("only the first leader loads");
+                    return self.lead_refresh(key, identifier, guard).await;
+                }
+                Flight::Follower(waiter) => {
+                    let previous = self.store.peek_stored(&identifier);
+                    // If the leader was cancelled, try again and maybe lead
+                    if let Some(landed) = waiter.landed().await {
+                        let (item, _) = landed?;
+                        return Ok((previous.map(|(item, _)| item.value), item.value));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs the reload for [`refresh_compare`](Self::refresh_compare) on behalf of every caller waiting on it
+    async fn lead_refresh(
         &self,
         key: K,
-    ) -> Result<Expiring<V>, Box<dyn std::error::Error + Send + Sync>> {
-        let identifier = (self.get_key_for_map)(&key);
+        identifier: String,
+        guard: LoadGuard<'_, (Expiring<V>, ValueSource)>,
+    ) -> Result<(Option<V>, V), CacheError> {
+        let started = self.store.next_tick();
+        let stored_key = self.stored_key(&key);
+        let item = match self.call_loader(key).await {
+            Ok(item) => self.store.transform(item),
+            Err(error) if guard.has_followers() => {
+                let error = error.into_shared();
+                guard.land(Err(error.clone_shared()));
+                return Err(error);
+            }
+            Err(error) => return Err(error),
+        };
+        if self.remembers_failures()
+            && let Ok(mut negative) = self.negative.write()
+        {
+            negative.remove(&identifier);
+        }
+
+        let item = self
+            .store
+            .apply_jitter(self.store.adapt_ttl(&identifier, item));
+        let (stored, previous) =
+            self.store
+                .replace_loaded(identifier.clone(), stored_key, item.clone(), started);
+        // If a newer value landed while this load was running, that's the new one
+        let item = if !stored
+            && !item.remaining_ttl().is_zero()
+            && let Some((newer, _)) = self.store.get_non_expired(&identifier)
+        {
+            newer
+        } else {
+            item
+        };
+        let loaded = (item, ValueSource::Fresh);
+        guard.land_value(&loaded);
+        Ok((previous.map(|item| item.value), loaded.0.value))
+    }
+
+    /// Reloads every live entry concurrently, reporting each identifier's result
+    ///
+    /// The identifiers are snapshotted up front and each is reloaded like
+    /// [`refresh_compare`](Self::refresh_compare), with retries and within
+    /// `max_concurrent_loads` if set. A failed reload leaves its entry alone.
+    /// Only entries whose key was remembered can be reloaded, see
+    /// [`remember_keys`](Self::remember_keys); the rest fail with
+    /// [`CacheError::NotCached`].
+    pub async fn reload_all(&self) -> Vec<(String, Result<(), CacheError>)> {
+        let (identifiers, keys): (Vec<_>, Vec<_>) = self.entry_keys().into_iter().unzip();
+        let results = join_all(keys.into_iter().map(|key| async move {
+            let key = key.ok_or(CacheError::NotCached)?;
+            self.refresh_compare(key).await.map(drop)
+        }))
+        .await;
+        identifiers.into_iter().zip(results).collect()
+    }
+
+    async fn get_with_outcome(
+        &self,
+        key: Cow<'_, K>,
+    ) -> Result<(Expiring<V>, CacheOutcome), CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        let (item, outcome, _) = self.lookup(key, identifier, None).await?;
+        Ok((item, outcome))
+    }
 
+    /// Looks up or loads `identifier`, waiting at most `patience` on another caller's load
+    async fn lookup(
+        &self,
+        key: Cow<'_, K>,
+        identifier: String,
+        patience: Option<Duration>,
+    ) -> Result<(Expiring<V>, CacheOutcome, ValueSource), CacheError> {
+        self.lookup_with(key, identifier, patience, |key| self.call_loader(key))
+            .await
+    }
+
+    /// Like [`lookup`](Self::lookup), calling `load` instead of the loader on a miss
+    async fn lookup_with<Fut>(
+        &self,
+        key: Cow<'_, K>,
+        identifier: String,
+        patience: Option<Duration>,
+        load: impl FnOnce(K) -> Fut,
+    ) -> Result<(Expiring<V>, CacheOutcome, ValueSource), CacheError>
+    where
+        Fut: Future<Output = Result<Expiring<V>, CacheError>>,
+    {
+        self.check_poisoned(&identifier)?;
         // Try to get non-expired item
-        if let Some(item) = self.get_non_expired(&identifier) {
-            return Ok(item);
+        if let Some((item, source)) = self.get_hit(&key, &identifier) {
+            return Ok((item, CacheOutcome::Hit, source));
+        }
+        self.stats.record_miss();
+
+        // Fail fast on a recently cached load failure
+        if let Some(error) = self.get_negative(&identifier) {
+            return Err(error);
+        }
+        if self.store.is_closed() {
+            return Err(CacheError::ShuttingDown);
         }
 
-        // Load and cache the item
-        self.load_and_cache_item(key, identifier).await
+        // Load and cache the item, or wait on a load that's already running
+        let started = Instant::now();
+        let key = key.into_owned();
+        let mut coalesced = false;
+        let mut load = Some(load);
+        let (item, source) = loop {
+            match self.in_flight.join(&identifier) {
+                Flight::Leader(guard) => {
+                    let load = load.take().expect("only the first leader loads");
+                    break self.lead_load(key, identifier, guard, load).await?;
+                }
+                Flight::Follower(waiter) => {
+                    if !coalesced {
+                        coalesced = true;
+                        self.stats.record_coalesced();
+                    }
+                    #[cfg(any(feature = "tokio", feature = "async-std"))]
+                    let landed = match patience {
+                        Some(patience) => {
+                            let left = patience.saturating_sub(started.elapsed());
+                            runtime::timeout(left, waiter.landed())
+                                .await
+                                .ok_or(CacheError::WaitTimeout(patience))?
+                        }
+                        None => waiter.landed().await,
+                    };
+                    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+                    let landed = {
+                        let _ = patience;
+                        waiter.landed().await
+                    };
+                    // If the leader was cancelled, try again and maybe lead
+                    if let Some(landed) = landed {
+                        break landed?;
+                    }
+                }
+            }
+        };
+        let outcome = CacheOutcome::Miss {
+            load_duration: started.elapsed(),
+        };
+        Ok((item, outcome, source))
+    }
+
+    /// Gets a live entry, starting a background refresh if one is due
+    fn get_hit(&self, key: &K, identifier: &str) -> Option<(Expiring<V>, ValueSource)> {
+        self.get_hit_with(key, identifier, Expiring::clone)
+    }
+
+    /// Like [`get_hit`](Self::get_hit), returning `f` of the entry instead of a clone
+    fn get_hit_with<U>(
+        &self,
+        key: &K,
+        identifier: &str,
+        f: impl FnOnce(&Expiring<V>) -> U,
+    ) -> Option<(U, ValueSource)> {
+        let (item, due_for_refresh) = self.store.get_non_expired_with(identifier, f)?;
+        #[cfg(any(feature = "tokio", feature = "async-std"))]
+        let source = if due_for_refresh {
+            self.spawn_refresh(key.clone(), identifier.to_string());
+            ValueSource::StaleRevalidating
+        } else {
+            ValueSource::Cached
+        };
+        #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+        let source = {
+            let _ = (key, due_for_refresh);
+            ValueSource::Cached
+        };
+        self.stats.record_hit();
+        Some((item, source))
+    }
+
+    /// Runs the load for `identifier` on behalf of every caller waiting on it
+    ///
+    /// Callers that joined get the leader's result. A loader error can't be
+    /// cloned, so when anyone joined, it's wrapped in a [`SharedError`](crate::SharedError)
+    /// for the leader too; a load nobody joined returns the loader's error unchanged.
+    /// If a live entry was stored since the caller missed, say by a load that
+    /// finished just before this one was registered, it's used without loading.
+    async fn lead_load<Fut>(
+        &self,
+        key: K,
+        identifier: String,
+        guard: LoadGuard<'_, (Expiring<V>, ValueSource)>,
+        load: impl FnOnce(K) -> Fut,
+    ) -> Result<(Expiring<V>, ValueSource), CacheError>
+    where
+        Fut: Future<Output = Result<Expiring<V>, CacheError>>,
+    {
+        let started = self.store.next_tick();
+        let stored_key = self.stored_key(&key);
+        let guard = match self
+            .resolve_without_loader(&identifier, &stored_key, guard, started)
+            .await
+        {
+            Ok(loaded) => return Ok(loaded),
+            Err(guard) => guard,
+        };
+        let result = load(key).await;
+        let loaded = self.land_load(identifier.clone(), stored_key, guard, result, started)?;
+        self.write_back(&identifier, &loaded).await;
+        Ok(loaded)
+    }
+
+    /// Settles a load the caller leads without calling the loader, if it can
+    ///
+    /// If a live entry was stored since the caller missed, or the backing store
+    /// has a live value, it's handed to everyone waiting. Otherwise the guard
+    /// is given back so the caller can call the loader. A miss on an entry
+    /// that only just expired is counted as a near miss.
+    async fn resolve_without_loader<'a>(
+        &self,
+        identifier: &str,
+        key: &Option<StoredKey>,
+        guard: LoadGuard<'a, (Expiring<V>, ValueSource)>,
+        started: u64,
+    ) -> Result<(Expiring<V>, ValueSource), LoadGuard<'a, (Expiring<V>, ValueSource)>> {
+        if let Some((item, _)) = self.store.get_non_expired(identifier) {
+            let loaded = (item, ValueSource::Cached);
+            guard.land_value(&loaded);
+            return Ok(loaded);
+        }
+        if self.store.expired_within(identifier, NEAR_MISS_WINDOW) {
+            self.stats.record_near_miss();
+        }
+        let Some(backing) = &self.store.hooks.backing else {
+            return Err(guard);
+        };
+
+        // The lookup takes a loader slot and is bounded by the load timeout
+        // too, and a backing store failure only means going to the loader
+        let stored = {
+            let _permit = self.store.load_permit().await;
+            self.with_load_timeout(async {
+                backing.get(identifier).await.map_err(CacheError::Load)
+            })
+            .await
+        };
+        match stored {
+            Ok(Some(item)) if !self.store.is_expired(&item) => {
+                self.store.insert_loaded(
+                    identifier.to_string(),
+                    key.clone(),
+                    item.clone(),
+                    started,
+                );
+                let loaded = (item, ValueSource::Backing);
+                guard.land_value(&loaded);
+                Ok(loaded)
+            }
+            _ => Err(guard),
+        }
+    }
+
+    /// Saves a value fresh from the loader to the backing store, if there is one
+    async fn write_back(&self, identifier: &str, (item, source): &(Expiring<V>, ValueSource)) {
+        if let Some(backing) = &self.store.hooks.backing
+            && *source == ValueSource::Fresh
+            && !item.remaining_ttl().is_zero()
+        {
+            let _ = backing.set(identifier, item).await;
+        }
+    }
+
+    /// Caches a finished load and hands its result to every caller waiting on it
+    fn land_load(
+        &self,
+        identifier: String,
+        key: Option<StoredKey>,
+        guard: LoadGuard<'_, (Expiring<V>, ValueSource)>,
+        result: Result<Expiring<V>, CacheError>,
+        started: u64,
+    ) -> Result<(Expiring<V>, ValueSource), CacheError> {
+        match self.cache_loaded(identifier, key, result, started) {
+            Ok(loaded) => {
+                guard.land_value(&loaded);
+                Ok(loaded)
+            }
+            Err(error) if guard.has_followers() => {
+                let error = error.into_shared();
+                guard.land(Err(error.clone_shared()));
+                Err(error)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Gets a value like [`get`](Self::get), but a miss's load survives this call being dropped
+    ///
+    /// On a miss the load runs in a spawned task, so if this future is dropped,
+    /// say because the caller timed out, the load still finishes: the value is
+    /// cached and any concurrent `get` waiting on the same key still receives
+    /// it. That needs a runtime, an `Arc` around the cache and `'static` key,
+    /// loader and key mapper types. The task keeps the cache alive until the
+    /// load is done. Hits are served directly without spawning anything. If the
+    /// task is dropped before it finishes, say because the runtime is shutting
+    /// down, this fails with [`CacheError::LoadCancelled`].
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn get_detached(self: &Arc<Self>, key: K) -> Result<V, CacheError>
+    where
+        K: 'static,
+        F: Send + Sync + 'static,
+        G: 'static,
+    {
+        let identifier = self.checked_identifier(&key)?;
+        if let Some((item, _)) = self.get_hit(&key, &identifier) {
+            return Ok(item.value);
+        }
+
+        let cache = Arc::clone(self);
+        let (sender, receiver) = oneshot::channel();
+        runtime::spawn(async move {
+            let _ = sender.send(cache.get(key).await);
+        });
+        receiver.await.unwrap_or(Err(CacheError::LoadCancelled))
+    }
+
+    /// Gets a value, loading it with `loader` instead of the cache's loader on a miss
+    ///
+    /// The loaded value is cached like any other, so later calls to
+    /// [`get`](Self::get) hit it. `loader` can only be called once, so the
+    /// retry policy doesn't apply, but the load timeout, the limit on
+    /// concurrent loads and the circuit breaker do. If the key is already
+    /// being loaded, this waits for that load instead of calling `loader`.
+    pub async fn get_or_load_with<Fut, E>(
+        &self,
+        key: K,
+        loader: impl FnOnce(K) -> Fut,
+    ) -> Result<V, CacheError>
+    where
+        Fut: Future<Output = Result<Expiring<V>, E>>,
+        E: Into<BoxError>,
+    {
+        let identifier = self.checked_identifier(&key)?;
+        let load = |key| {
+            self.through_breaker(self.run_load(catch_loader_panic(|| {
+                let load = loader(key);
+                async move { load.await.map_err(Into::into) }
+            })))
+        };
+        let (item, _, _) = self
+            .lookup_with(Cow::Owned(key), identifier, None, load)
+            .await?;
+        Ok(item.value)
+    }
+
+    /// Gets a value from the cache, computing it with `f` on a miss
+    ///
+    /// `f` runs synchronously in place of the loader and its result is cached
+    /// for `ttl`. Otherwise this is a lookup like [`get`](Self::get): hits and
+    /// misses are counted, concurrent misses wait for one call to `f`, and an
+    /// error from `f` is negatively cached like a loader's when
+    /// `negative_ttl` is set. A zero `ttl` returns the value without caching it.
+    pub async fn get_or_insert_with<E>(
+        &self,
+        key: K,
+        ttl: Duration,
+        f: impl FnOnce() -> Result<V, E>,
+    ) -> Result<V, CacheError>
+    where
+        E: Into<BoxError>,
+    {
+        let identifier = self.checked_identifier(&key)?;
+        let load = |_| async move {
+            f().map(|value| Expiring::with_duration(value, ttl))
+                .map_err(|error| CacheError::Load(error.into()))
+        };
+        let (item, _, _) = self
+            .lookup_with(Cow::Owned(key), identifier, None, load)
+            .await?;
+        Ok(item.value)
+    }
+
+    /// Inserts an item into the cache, replacing any existing value for the key
+    pub fn insert(&self, key: K, item: Expiring<V>) {
+        if let Ok(identifier) = self.identifier(&key) {
+            self.store
+                .insert_item(identifier, self.stored_key(&key), item);
+        }
+    }
+
+    /// Inserts a batch of items, taking each shard's write lock only once
+    ///
+    /// Cheaper than calling [`insert`](Self::insert) in a loop when warming the
+    /// cache. If the batch holds the same key more than once, the last one wins.
+    pub fn insert_many(&self, entries: impl IntoIterator<Item = (K, Expiring<V>)>) {
+        let items = entries
+            .into_iter()
+            .filter_map(|(key, item)| {
+                Some((self.identifier(&key).ok()?, self.stored_key(&key), item))
+            })
+            .collect();
+        self.store.insert_items(items);
+    }
+
+    /// Inserts a value that expires after the configured default TTL
+    ///
+    /// Without a default TTL the value never expires.
+    pub fn put(&self, key: K, value: V) {
+        let item = match self.store.options.default_ttl {
+            Some(ttl) => Expiring::with_duration(value, ttl),
+            None => Expiring::never(value),
+        };
+        self.insert(key, item);
+    }
+
+    /// Modifies a cached value in place, without reloading or reinserting it
+    ///
+    /// `f` runs under the shard's write lock, so keep it short. Returns `false`
+    /// without calling `f` if there's no live entry for `key`. The entry's
+    /// expiry is left as it was.
+    pub fn update(&self, key: &K, f: impl FnOnce(&mut V)) -> bool {
+        let Ok(identifier) = self.identifier(key) else {
+            return false;
+        };
+        self.store.update(&identifier, f)
+    }
+
+    /// Returns a handle to the entry for `key`, for chaining reads and writes to it
+    ///
+    /// Fails only if the key mapper rejects `key`. See [`EntryHandle`].
+    pub fn entry(&self, key: K) -> Result<EntryHandle<'_, V, S>, CacheError> {
+        let identifier = self.checked_identifier(&key)?;
+        Ok(EntryHandle::new(
+            &self.store,
+            identifier,
+            self.stored_key(&key),
+        ))
+    }
+
+    /// Makes a cached entry expire at the absolute time `when`
+    ///
+    /// Returns `false` without changing anything if there's no live entry for
+    /// `key`. A `when` in the past makes the entry count as expired from now on,
+    /// so the next `get` reloads it.
+    pub fn expire_at(&self, key: &K, when: SystemTime) -> bool {
+        let Ok(identifier) = self.identifier(key) else {
+            return false;
+        };
+        self.store.set_expiry(&identifier, Some(when))
     }
 
     /// Deletes an item from the cache
     pub fn delete(&self, key: K) {
-        let identifier = (self.get_key_for_map)(&key);
-        if let Ok(mut map) = self.map.write() {
-            map.remove(&identifier);
+        let Ok(identifier) = self.identifier(&key) else {
+            return;
+        };
+        let removed = self.store.remove(&identifier);
+        if let Ok(mut negative) = self.negative.write() {
+            negative.remove(&identifier);
+        }
+        if let Some(entry) = removed {
+            self.store
+                .notify_evicted(vec![(identifier, entry.item, EvictionReason::Manual)]);
+        }
+    }
+
+    /// Stops starting loads, then waits up to `grace` for the running ones to finish
+    ///
+    /// Afterwards misses fail with [`CacheError::ShuttingDown`] instead of
+    /// loading, and no background refreshes start, while hits are still
+    /// served from the entries already stored. Loads that finish within
+    /// `grace` are stored and handed to their callers as usual; ones still
+    /// running after it are left to finish on their own. Background refreshes
+    /// that are already running aren't waited for. Clones of the cache share
+    /// the shutdown.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn shutdown(&self, grace: Duration) {
+        self.store.close();
+        let _ = runtime::timeout(grace, self.in_flight.settled()).await;
+    }
+
+    /// Deletes an item and waits for a load of it that's already running
+    ///
+    /// With plain [`delete`](Self::delete), a `get` that was loading the key
+    /// stores its value right after the delete. This waits for such a load to
+    /// finish and removes what it stored, so the key is absent when this
+    /// returns unless a new load started meanwhile. Callers waiting on that
+    /// load still receive its value. Background refreshes never bring back a
+    /// deleted entry. The key is removed from the backing store too, if one
+    /// is set.
+    pub async fn delete_await(&self, key: K) {
+        let Ok(identifier) = self.identifier(&key) else {
+            return;
+        };
+        let running = self.in_flight.running(&identifier);
+        self.delete(key.clone());
+        if let Some(waiter) = running {
+            waiter.landed().await;
+            self.delete(key);
+        }
+        if let Some(backing) = &self.store.hooks.backing {
+            let _ = backing.delete(&identifier).await;
+        }
+    }
+
+    /// Clears all items from the cache, returning how many were removed
+    ///
+    /// Each shard's write lock is taken once, so the count is exactly what this
+    /// call removed even while other tasks keep inserting. Loads already running
+    /// still hand their values to their callers, but don't store them, so the
+    /// cache doesn't fill back up with values loaded before the clear.
+    pub fn clear(&self) -> usize {
+        let evicted = self.store.drain(EvictionReason::Manual);
+        if let Ok(mut negative) = self.negative.write() {
+            negative.clear();
+        }
+        let removed = evicted.len();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Removes every entry in this cache's namespace, returning how many were removed
+    ///
+    /// Entries are removed with [`EvictionReason::Manual`], and cached load
+    /// failures in the namespace are forgotten too. Without a namespace this
+    /// removes everything, like [`clear`](Self::clear).
+    pub fn invalidate_namespace(&self) -> usize {
+        let Some(namespace) = &self.store.options.namespace else {
+            return self.clear();
+        };
+        let prefix = format!("{}:", namespace);
+        let evicted = self.store.remove_prefixed(&prefix);
+        if let Ok(mut negative) = self.negative.write() {
+            negative.retain(|identifier, _| !identifier.starts_with(&prefix));
+        }
+        let removed = evicted.len();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Removes every entry whose identifier starts with `prefix`, returning how many were removed
+    ///
+    /// Suits hierarchical identifiers such as `user:123:profile`, where
+    /// `remove_prefix("user:123:")` drops everything cached for one user. The
+    /// prefix is matched against the full identifier, including the
+    /// namespace if one is set. Entries are removed with
+    /// [`EvictionReason::Manual`] and matching cached load failures are
+    /// forgotten too. Like [`clear`](Self::clear), loads already running
+    /// don't store their results. Takes O(log n + k) with
+    /// [`CacheBuilder::ordered_index`](crate::CacheBuilder::ordered_index),
+    /// and scans every entry otherwise.
+    pub fn remove_prefix(&self, prefix: &str) -> usize {
+        let evicted = self.store.remove_prefixed(prefix);
+        if let Ok(mut negative) = self.negative.write() {
+            negative.retain(|identifier, _| !identifier.starts_with(prefix));
+        }
+        let removed = evicted.len();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Keeps only the entries for which `f` returns true, returning how many were removed
+    ///
+    /// Like `HashMap::retain`, but `f` sees every stored entry, expired or not,
+    /// by identifier and may change the ones it keeps, for example to push out
+    /// their expiry. `max_age` still caps whatever expiry `f` sets. Each
+    /// shard's write lock is held while `f` runs over its entries, so `f` must
+    /// not call back into the cache.
+    pub fn retain(&self, f: impl FnMut(&str, &mut Expiring<V>) -> bool) -> usize {
+        let evicted = self.store.retain(f);
+        let removed = evicted
+            .iter()
+            .filter(|(_, _, reason)| *reason == EvictionReason::Manual)
+            .count();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Removes and returns every entry, including expired ones
+    ///
+    /// Values are moved out rather than cloned, which makes this the cheapest way
+    /// to flush the cache on shutdown. Since the caller takes ownership of the
+    /// entries, `on_evict` isn't called and no eviction events are sent for them.
+    pub fn drain(&self) -> Vec<(String, Expiring<V>)> {
+        let drained = self.store.drain(EvictionReason::Manual);
+        if let Ok(mut negative) = self.negative.write() {
+            negative.clear();
+        }
+        drained
+            .into_iter()
+            .map(|(identifier, item, _)| (identifier, item))
+            .collect()
+    }
+
+    /// Consumes the cache, returning its live values by identifier
+    ///
+    /// When this is the last handle to the cache, the values are moved out
+    /// rather than cloned and expired entries are dropped. If clones of the
+    /// cache are still around, including ones held by background refreshes,
+    /// the live values are cloned instead and the other handles keep their
+    /// entries.
+    pub fn into_hashmap(self) -> HashMap<String, V> {
+        match Arc::try_unwrap(self.store) {
+            Ok(store) => store
+                .drain(EvictionReason::Manual)
+                .into_iter()
+                .filter(|(_, item, _)| !store.is_expired(item))
+                .map(|(identifier, item, _)| (identifier, item.value))
+                .collect(),
+            Err(store) => store
+                .collect_live(|identifier, item| (identifier.to_string(), item.value.clone()))
+                .into_iter()
+                .collect(),
         }
     }
 
     /// Clears all items from the cache
+    ///
+    /// Same as [`clear`](Self::clear), without the count.
     pub fn delete_all(&self) {
-        if let Ok(mut map) = self.map.write() {
-            map.clear();
+        self.clear();
+    }
+
+    /// Evicts entries until at most `target_size` are left, returning how many were removed
+    ///
+    /// Unlike [`purge_expired`](Self::purge_expired) this removes live entries
+    /// too if it has to, which suits reacting to memory pressure. Expired
+    /// entries go first, then the rest in eviction policy order, and the
+    /// shards' spare capacity is released afterwards. `on_evict` sees live
+    /// entries removed this way with [`EvictionReason::Capacity`].
+    pub fn shrink_to(&self, target_size: usize) -> usize {
+        let evicted = self.store.shrink_to(target_size);
+        let removed = evicted.len();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Removes all expired items from the cache, returning how many were removed
+    ///
+    /// Items that never expire are always kept. Each shard keeps its entries
+    /// queued by expiry, so this only visits the ones due rather than scanning
+    /// the whole cache. Each removed entry is passed
+    /// to `on_evict` with [`EvictionReason::Expired`] and published as a
+    /// [`CacheEvent::Expired`], once all shard locks have been released.
+    pub fn purge_expired(&self) -> usize {
+        let evicted = self.store.purge_expired();
+        let removed = evicted.len();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Returns a copy of every live entry as identifier/item pairs
+    ///
+    /// Each shard's read lock is held while its entries are cloned, so this is an
+    /// O(n) operation that blocks writers to that shard for the duration. The
+    /// result is consistent per shard but not across shards.
+    pub fn snapshot(&self) -> Vec<(String, Expiring<V>)> {
+        self.store
+            .collect_live(|identifier, item| (identifier.to_string(), item.clone()))
+    }
+
+    /// Returns every live value whose identifier is within `bounds`, sorted by identifier
+    ///
+    /// Bounds compare against full identifiers, including the namespace if
+    /// one is set, so `range("user:".to_string().."user;".to_string())` finds
+    /// every identifier starting with `user:`. Takes O(log n + k) with
+    /// [`CacheBuilder::ordered_index`](crate::CacheBuilder::ordered_index),
+    /// and scans every entry otherwise. Lookups through this don't count as
+    /// hits.
+    pub fn range(&self, bounds: impl RangeBounds<String>) -> Vec<(String, V)> {
+        let bounds = (
+            bounds.start_bound().map(String::as_str),
+            bounds.end_bound().map(String::as_str),
+        );
+        self.store.collect_range(bounds, |identifier, item| {
+            (identifier.to_string(), item.value.clone())
+        })
+    }
+
+    /// Formats every live entry as a map from identifier to item
+    ///
+    /// `Cache`'s own `Debug` output only summarizes the cache so it works for
+    /// any `V`; use this when the values themselves are worth printing.
+    pub fn debug_entries(&self) -> impl fmt::Debug + use<K, V, F, G, S>
+    where
+        V: fmt::Debug,
+    {
+        DebugEntries(self.snapshot())
+    }
+
+    /// Returns the identifiers of every live entry
+    pub fn keys(&self) -> Vec<String> {
+        self.store
+            .collect_live(|identifier, _| identifier.to_string())
+    }
+
+    /// Returns the identifiers of live entries expiring within `window`, soonest first
+    ///
+    /// Entries that never expire are left out. Meant for scheduling reloads
+    /// ahead of demand, for example with
+    /// [`refresh_compare`](Self::refresh_compare), without the cache spawning
+    /// tasks itself. Each shard's read lock is taken once and nothing counts
+    /// as a hit.
+    pub fn expiring_within(&self, window: Duration) -> Vec<String> {
+        let mut expiring: Vec<(Duration, String)> = self
+            .store
+            .collect_live(|identifier, item| {
+                let remaining = item.remaining_ttl();
+                (item.expires_at.is_some() && remaining < window)
+                    .then(|| (remaining, identifier.to_string()))
+            })
+            .into_iter()
+            .flatten()
+            .collect();
+        expiring.sort();
+        expiring
+            .into_iter()
+            .map(|(_, identifier)| identifier)
+            .collect()
+    }
+
+    /// Returns the original key of every live entry
+    ///
+    /// Needs [`remember_keys`](Self::remember_keys); without it this is always
+    /// empty.
+    pub fn keys_typed(&self) -> Vec<K> {
+        self.live_keys().into_iter().map(|(_, key)| key).collect()
+    }
+
+    /// Removes every live entry whose original key matches `f`, returning how many were removed
+    ///
+    /// Like [`retain`](Self::retain) but over keys instead of identifiers.
+    /// Entries are removed with [`EvictionReason::Manual`]. Needs
+    /// [`remember_keys`](Self::remember_keys); without it nothing is removed.
+    pub fn delete_where(&self, f: impl Fn(&K) -> bool) -> usize {
+        let mut evicted = Vec::new();
+        for (identifier, key) in self.live_keys() {
+            if !f(&key) {
+                continue;
+            }
+            if let Ok(mut negative) = self.negative.write() {
+                negative.remove(&identifier);
+            }
+            if let Some(entry) = self.store.remove(&identifier) {
+                evicted.push((identifier, entry.item, EvictionReason::Manual));
+            }
         }
+        let removed = evicted.len();
+        self.store.notify_evicted(evicted);
+        removed
+    }
+
+    /// Pairs every live identifier with its remembered key, skipping ones without
+    fn live_keys(&self) -> Vec<(String, K)> {
+        self.entry_keys()
+            .into_iter()
+            .filter_map(|(identifier, key)| Some((identifier, key?)))
+            .collect()
+    }
+
+    /// Pairs every live identifier with the key its entry remembers, if any
+    fn entry_keys(&self) -> Vec<(String, Option<K>)> {
+        let recover = self.key_codec.map(|codec| codec.recover);
+        self.store.collect_live_entries(|identifier, entry| {
+            let key = recover
+                .zip(entry.key.as_ref())
+                .and_then(|(recover, key)| recover(key));
+            (identifier.to_string(), key)
+        })
+    }
+
+    /// Returns a copy of every live value
+    pub fn values(&self) -> Vec<V> {
+        self.store.collect_live(|_, item| item.value.clone())
+    }
+
+    /// Exports every live entry for persisting, see [`CacheSnapshot`]
+    pub fn dump(&self) -> CacheSnapshot<V> {
+        let entries = self
+            .store
+            .collect_live_entries(|identifier, entry| SnapshotEntry {
+                identifier: identifier.to_string(),
+                value: entry.item.value.clone(),
+                expires_at: entry.item.expires_at,
+                soft_expires_at: entry.item.soft_expires_at(),
+                last_accessed: Some(entry.last_accessed()),
+            });
+        CacheSnapshot { entries }
+    }
+
+    /// Imports entries from a snapshot, returning how many were restored
+    ///
+    /// Entries are stored under their saved identifiers without going through
+    /// the key mapper. Entries that expired since the snapshot was taken are
+    /// skipped.
+    pub fn restore(&self, snapshot: CacheSnapshot<V>) -> usize {
+        let items: Vec<_> = snapshot
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let item = Expiring {
+                    expires_at: entry.expires_at,
+                    soft_expires_at: entry.soft_expires_at,
+                    value: entry.value,
+                };
+                (entry.identifier, None, item)
+            })
+            .filter(|(_, _, item)| !self.store.is_expired(item))
+            .collect();
+        let restored = items.len();
+        self.store.insert_items(items);
+        restored
+    }
+
+    /// Subscribes to insert and eviction events
+    ///
+    /// Events are only delivered to receivers that exist when they're sent. A
+    /// receiver that falls more than `event_capacity` events behind skips the
+    /// oldest ones and sees `RecvError::Lagged`; the cache itself never waits.
+    pub fn subscribe(&self) -> broadcast::Receiver<CacheEvent> {
+        self.store.subscribe()
+    }
+
+    /// Spawns a background task that calls [`purge_expired`](Self::purge_expired) every `interval`
+    ///
+    /// The task only holds a weak reference, so it stops on its own once the
+    /// last `Arc` to the cache is dropped. Entries it purges are announced to
+    /// `on_evict` and subscribers like with a manual purge.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub fn spawn_janitor(self: &Arc<Self>, interval: Duration)
+    where
+        K: 'static,
+        F: Send + Sync + 'static,
+        G: 'static,
+    {
+        let cache = Arc::downgrade(self);
+        runtime::spawn(async move {
+            loop {
+                runtime::sleep(interval).await;
+                match cache.upgrade() {
+                    Some(cache) => {
+                        cache.purge_expired();
+                    }
+                    None => break,
+                }
+            }
+        });
     }
 
     /// Gets the current size of the cache
     pub fn size(&self) -> usize {
-        self.map.read().map(|map| map.len()).unwrap_or(0)
+        self.store.size()
     }
 
-    fn get_non_expired(&self, identifier: &str) -> Option<Expiring<V>> {
-        if let Ok(map) = self.map.read() {
-            if let Some(item) = map.get(identifier) {
-                if !item.is_expired() {
-                    return Some(item.clone());
-                }
+    /// Gets the maximum number of entries, or `None` if the cache is unbounded
+    ///
+    /// Every insert evicts down to this before returning, so
+    /// [`size`](Self::size) never exceeds it once writes have finished.
+    pub fn capacity(&self) -> Option<usize> {
+        self.store.options.max_capacity
+    }
+
+    /// Gets how many more entries fit before inserts start evicting
+    ///
+    /// `None` if the cache is unbounded. Expired entries that haven't been
+    /// removed yet still take up room, and a weight limit may evict sooner.
+    pub fn remaining_capacity(&self) -> Option<usize> {
+        self.capacity()
+            .map(|capacity| capacity.saturating_sub(self.size()))
+    }
+
+    /// Gets the total weight of all entries, as measured by the weigher
+    ///
+    /// Always zero when no weigher is configured.
+    pub fn total_weight(&self) -> usize {
+        self.store.total_weight()
+    }
+
+    /// Gets the number of entries that haven't expired
+    ///
+    /// Unlike [`size`](Self::size) this skips expired entries that haven't been
+    /// removed yet, and unlike [`purge_expired`](Self::purge_expired) it doesn't
+    /// modify the cache.
+    pub fn active_size(&self) -> usize {
+        self.store.active_size()
+    }
+
+    /// Gets the hit, miss and coalesced-load counts since the cache was created
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+
+    /// Gets the loader call duration that a fraction `p` of calls finished within
+    ///
+    /// `p` runs from `0.0` to `1.0`, so `0.99` is the p99. Every loader call
+    /// counts, including retries, failures and background refreshes. Durations
+    /// are bucketed, and the upper bound of the bucket is returned, which is
+    /// at most 25% above the true value. `None` until the loader has run.
+    pub fn load_latency_percentile(&self, p: f64) -> Option<Duration> {
+        self.store.load_latency.percentile(p)
+    }
+
+    /// Gets the number of shards the cache storage is split into
+    pub fn shard_count(&self) -> usize {
+        self.store.shard_count()
+    }
+
+    /// Maps a key to the identifier it's stored under, prefixed by the namespace if set
+    fn identifier(&self, key: &K) -> Result<String, CacheError> {
+        let mapped = self.get_key_for_map.map_key(key)?;
+        let identifier = match &self.store.options.namespace {
+            Some(namespace) => format!("{}:{}", namespace, mapped),
+            None => mapped,
+        };
+        if let Some(collisions) = &self.collisions {
+            collisions.check(&identifier, key)?;
+        }
+        Ok(identifier)
+    }
+
+    /// The key to keep with an entry stored under `key`, if keys are remembered
+    fn stored_key(&self, key: &K) -> Option<StoredKey> {
+        self.key_codec.map(|codec| (codec.erase)(key))
+    }
+
+    /// Like [`identifier`](Self::identifier), also failing with [`CacheError::LockPoisoned`]
+    /// under [`PoisonPolicy::Error`] if the identifier's shard is poisoned
+    fn checked_identifier(&self, key: &K) -> Result<String, CacheError> {
+        let identifier = self.identifier(key)?;
+        self.check_poisoned(&identifier)?;
+        Ok(identifier)
+    }
+
+    /// Fails under [`PoisonPolicy::Error`] if `identifier`'s shard is poisoned,
+    /// since reading it would panic
+    fn check_poisoned(&self, identifier: &str) -> Result<(), CacheError> {
+        if self.store.options.poison_policy == PoisonPolicy::Error
+            && self.store.is_poisoned(identifier)
+        {
+            return Err(CacheError::LockPoisoned);
+        }
+        Ok(())
+    }
+
+    /// Whether load failures are kept in the negative cache
+    fn remembers_failures(&self) -> bool {
+        self.store.options.negative_ttl.is_some() || self.store.options.failure_cooldown.is_some()
+    }
+
+    fn get_negative(&self, identifier: &str) -> Option<CacheError> {
+        if let Ok(negative) = self.negative.read()
+            && let Some(failure) = negative.get(identifier)
+            && !failure.is_expired()
+        {
+            if let CacheError::InCooldown { .. } = failure.value {
+                return Some(CacheError::InCooldown {
+                    retry_after: failure.remaining_ttl(),
+                });
             }
+            return Some(failure.value.clone_shared());
         }
         None
     }
 
-    async fn load_and_cache_item(
+    /// Reloads an entry in the background, unless a refresh for it is already running
+    ///
+    /// The refresh calls the loader once, without retries or a timeout. If it
+    /// fails or panics, the current value is kept until it expires, and if the
+    /// entry is removed while it runs, its result is dropped.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    fn spawn_refresh(&self, key: K, identifier: String) {
+        if self.store.is_closed() {
+            return;
+        }
+        let Some(refresh) = self.store.start_refresh(&identifier) else {
+            return;
+        };
+        if self
+            .store
+            .breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.check().is_err())
+        {
+            return;
+        }
+        let started = self.store.next_tick();
+        let stored_key = self.stored_key(&key);
+        // A panicking loader only skips the refresh; the hit is still served
+        let Ok(load) = panic::catch_unwind(AssertUnwindSafe(|| (self.load)(key))) else {
+            return;
+        };
+        runtime::spawn(async move {
+            let _permit = refresh.store.load_permit().await;
+            let load_started = Instant::now();
+            let result = catch_loader_panic(|| load).await;
+            refresh.store.load_latency.record(load_started.elapsed());
+            if let Some(breaker) = &refresh.store.breaker {
+                breaker.record(&result);
+            }
+            if let Ok(item) = result
+                && !item.remaining_ttl().is_zero()
+            {
+                let item = refresh.store.transform(item);
+                let item = refresh
+                    .store
+                    .apply_jitter(refresh.store.adapt_ttl(&identifier, item));
+                refresh
+                    .store
+                    .insert_refreshed(identifier, stored_key, item, started);
+            }
+        });
+    }
+
+    /// Calls the loader, retrying failures according to the retry policy
+    async fn call_loader(&self, key: K) -> Result<Expiring<V>, CacheError> {
+        #[cfg(any(feature = "tokio", feature = "async-std"))]
+        if let Some(retry) = &self.store.hooks.retry {
+            let mut attempt = 1;
+            loop {
+                let error = match self.call_loader_once(key.clone()).await {
+                    Err(CacheError::CircuitOpen) => return Err(CacheError::CircuitOpen),
+                    Err(error) => error,
+                    result => return result,
+                };
+                // Predicates see the loader's own error, or the CacheError for timeouts and panics
+                let seen: &(dyn Error + Send + Sync + 'static) = match &error {
+                    CacheError::Load(inner) => inner.as_ref(),
+                    other => other,
+                };
+                if !retry.should_retry(attempt, seen) {
+                    return Err(error);
+                }
+                runtime::sleep(retry.delay_after(attempt)).await;
+                attempt += 1;
+            }
+        }
+
+        self.call_loader_once(key).await
+    }
+
+    /// Calls the loader once, turning a panic into [`CacheError::LoaderPanicked`]
+    async fn call_loader_once(&self, key: K) -> Result<Expiring<V>, CacheError> {
+        self.through_breaker(self.run_load(catch_loader_panic(|| (self.load)(key))))
+            .await
+    }
+
+    /// Runs a loader call behind the circuit breaker, if one is set
+    ///
+    /// An open circuit fails fast without running `call`, and every call's
+    /// outcome is recorded.
+    async fn through_breaker<T>(
+        &self,
+        call: impl Future<Output = Result<T, CacheError>>,
+    ) -> Result<T, CacheError> {
+        let Some(breaker) = &self.store.breaker else {
+            return call.await;
+        };
+        breaker.check()?;
+        let result = call.await;
+        breaker.record(&result);
+        result
+    }
+
+    /// Runs a load once a loader slot is free, giving up once the load timeout passes
+    ///
+    /// The load's duration is recorded in the load latency histogram whether
+    /// it succeeds or not, leaving out any wait for a loader slot.
+    async fn run_load<T>(
+        &self,
+        load: impl Future<Output = Result<T, CacheError>>,
+    ) -> Result<T, CacheError> {
+        let _permit = self.store.load_permit().await;
+        let started = Instant::now();
+        let result = self.with_load_timeout(load).await;
+        self.store.load_latency.record(started.elapsed());
+        result
+    }
+
+    async fn with_load_timeout<T>(
+        &self,
+        load: impl Future<Output = Result<T, CacheError>>,
+    ) -> Result<T, CacheError> {
+        #[cfg(any(feature = "tokio", feature = "async-std"))]
+        if let Some(limit) = self.store.options.load_timeout {
+            return runtime::timeout(limit, load)
+                .await
+                .unwrap_or_else(|| Err(CacheError::LoadTimeout(limit)));
+        }
+
+        load.await
+    }
+
+    /// Stores the result of a load that started at tick `started`
+    fn cache_loaded(
         &self,
-        key: K,
         identifier: String,
-    ) -> Result<Expiring<V>, Box<dyn std::error::Error + Send + Sync>> {
-        let item = (self.load)(key).await?;
+        key: Option<StoredKey>,
+        result: Result<Expiring<V>, CacheError>,
+        started: u64,
+    ) -> Result<(Expiring<V>, ValueSource), CacheError> {
+        let item = match result {
+            Ok(item) => self.store.transform(item),
+            Err(error) => {
+                if let ReloadErrorPolicy::StaleFallback { grace } =
+                    self.store.options.on_reload_error
+                    && let Some(stale) = self.store.revive_stale(&identifier, grace)
+                {
+                    return Ok((stale, ValueSource::StaleFallbackOnError));
+                }
+                // The breaker already fails fast, and knows when to try again
+                if matches!(error, CacheError::CircuitOpen) {
+                    return Err(error);
+                }
+                let options = &self.store.options;
+                let ttl = match (options.failure_cooldown, options.negative_ttl) {
+                    (Some(cooldown), _) => cooldown,
+                    (None, Some(ttl)) => ttl,
+                    (None, None) => return Err(error),
+                };
+                let error = error.into_shared();
+                // Under a cooldown only the failing call sees the loader's error
+                let failure = match options.failure_cooldown {
+                    Some(_) => CacheError::InCooldown { retry_after: ttl },
+                    None => error.clone_shared(),
+                };
+                if let Ok(mut negative) = self.negative.write() {
+                    negative.insert(identifier, Expiring::with_duration(failure, ttl));
+                }
+                return Err(error);
+            }
+        };
 
-        if let Ok(mut map) = self.map.write() {
-            map.insert(identifier, item.clone());
+        if self.remembers_failures()
+            && let Ok(mut negative) = self.negative.write()
+        {
+            negative.remove(&identifier);
         }
 
-        Ok(item)
+        // A value that's already expired would only be reloaded on the next get
+        if item.remaining_ttl().is_zero() {
+            return Ok((item, ValueSource::Fresh));
+        }
+
+        // If a newer value landed while this load was running, keep and return that
+        let item = self
+            .store
+            .apply_jitter(self.store.adapt_ttl(&identifier, item));
+        if !self
+            .store
+            .insert_loaded(identifier.clone(), key, item.clone(), started)
+            && let Some((newer, _)) = self.store.get_non_expired(&identifier)
+        {
+            return Ok((newer, ValueSource::Fresh));
+        }
+
+        Ok((item, ValueSource::Fresh))
     }
 }
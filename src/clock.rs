@@ -0,0 +1,50 @@
+//! Coarse clock for cheap expiry checks on the read path
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// A wall-clock reading refreshed by a background thread once per granularity
+///
+/// Reading it is a single atomic load instead of a clock call. The thread only
+/// holds a weak reference and exits on its next tick once the clock is dropped.
+pub(crate) struct CoarseClock {
+    nanos: AtomicU64,
+}
+
+impl CoarseClock {
+    pub(crate) fn start(granularity: Duration) -> Arc<Self> {
+        let clock = Arc::new(Self {
+            nanos: AtomicU64::new(nanos_since_epoch(SystemTime::now())),
+        });
+        let weak: Weak<Self> = Arc::downgrade(&clock);
+        let granularity = granularity.max(Duration::from_millis(1));
+        thread::Builder::new()
+            .name("cache-rs-clock".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(granularity);
+                    let Some(clock) = weak.upgrade() else {
+                        break;
+                    };
+                    clock
+                        .nanos
+                        .store(nanos_since_epoch(SystemTime::now()), Ordering::Relaxed);
+                }
+            })
+            .expect("failed to spawn the cache clock thread");
+        clock
+    }
+
+    pub(crate) fn now(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+pub(crate) fn nanos_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+        })
+}
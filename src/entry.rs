@@ -0,0 +1,77 @@
+//! A handle to one cache entry, see [`Cache::entry`](crate::Cache::entry)
+
+use crate::cache::Expiring;
+use crate::store::{Store, StoredKey};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, SystemTime};
+
+/// A handle to the entry for one key, for chaining reads and writes to it
+///
+/// Like [`std::collections::hash_map::Entry`]. Each method runs under a
+/// single acquisition of the shard lock, so a compound step like
+/// [`or_insert_with`](Self::or_insert_with) is atomic, but nothing stays
+/// locked between calls, so a chain isn't atomic as a whole. None of the
+/// methods are async, so no lock is ever held across an await. To load a
+/// missing value with the cache's loader, use [`Cache::get`](crate::Cache::get),
+/// which coalesces concurrent loads.
+pub struct EntryHandle<'a, V, S = RandomState> {
+    store: &'a Store<V, S>,
+    identifier: String,
+    key: Option<StoredKey>,
+}
+
+impl<'a, V, S> EntryHandle<'a, V, S>
+where
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    pub(crate) fn new(store: &'a Store<V, S>, identifier: String, key: Option<StoredKey>) -> Self {
+        Self {
+            store,
+            identifier,
+            key,
+        }
+    }
+
+    /// The identifier the key maps to
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Modifies the live value in place, keeping its expiry
+    ///
+    /// Does nothing if there's no live entry. See [`Cache::update`](crate::Cache::update).
+    pub fn and_modify(self, f: impl FnOnce(&mut V)) -> Self {
+        self.store.update(&self.identifier, f);
+        self
+    }
+
+    /// Returns the live value, or caches the one `f` computes for `ttl`
+    ///
+    /// The check and the insert happen under one shard write lock, so when
+    /// several callers race on a missing key only one runs `f` and the rest
+    /// get its value. `f` runs under the lock, so keep it short.
+    pub fn or_insert_with(self, ttl: Duration, f: impl FnOnce() -> V) -> V {
+        self.store
+            .get_or_insert_item(&self.identifier, self.key, || {
+                Expiring::with_duration(f(), ttl)
+            })
+    }
+
+    /// How long the live entry has left, `Duration::MAX` if it never expires
+    ///
+    /// `None` if there's no live entry. Doesn't count as a use of the entry.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.store.time_to_live(&self.identifier)
+    }
+
+    /// Makes the live entry expire `ttl` from now, returning whether there was one
+    ///
+    /// A `ttl` too long to represent, such as the `Duration::MAX` that
+    /// [`ttl`](Self::ttl) reports for entries that never expire, makes the entry never expire.
+    pub fn set_ttl(&self, ttl: Duration) -> bool {
+        self.store
+            .set_expiry(&self.identifier, SystemTime::now().checked_add(ttl))
+    }
+}
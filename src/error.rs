@@ -0,0 +1,207 @@
+use crate::cache::BoxError;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Why a cache operation failed
+///
+/// [`Load`](CacheError::Load) carries the loader's own error, so "the backend
+/// said no" can be told apart from failures in the cache itself.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CacheError {
+    /// The loader returned an error
+    ///
+    /// When the same failure is handed to several callers, such as ones that
+    /// joined the same load or a failure served from the negative cache, the
+    /// error is a [`SharedError`] wrapping the loader's error.
+    Load(BoxError),
+    /// The loader didn't finish within the configured `load_timeout`
+    LoadTimeout(Duration),
+    /// The key has no live entry and loading wasn't allowed
+    NotCached,
+    /// The loader panicked; carries the panic message
+    LoaderPanicked(String),
+    /// A lock was poisoned by a panic while it was held, see [`PoisonPolicy`](crate::PoisonPolicy)
+    LockPoisoned,
+    /// The caller gave up waiting on another caller's load, which keeps running
+    WaitTimeout(Duration),
+    /// The key mapper rejected the key, see [`TryKeyMapper`](crate::TryKeyMapper)
+    InvalidKey(BoxError),
+    /// A different key already maps to this identifier, see
+    /// [`Cache::detect_key_collisions`](crate::Cache::detect_key_collisions)
+    KeyCollision(String),
+    /// Loading one of the keys passed to [`Cache::get_all`](crate::Cache::get_all) failed
+    BatchFailed {
+        /// Identifier of the key that failed
+        identifier: String,
+        /// Why it failed
+        source: Box<CacheError>,
+    },
+    /// The loader kept failing, so the circuit breaker is skipping it for now,
+    /// see [`CircuitBreaker`](crate::CircuitBreaker)
+    CircuitOpen,
+    /// The cache is shutting down and no longer starts loads, see
+    /// [`Cache::shutdown`](crate::Cache::shutdown)
+    ShuttingDown,
+    /// The deadline passed to [`Cache::get_by_deadline`](crate::Cache::get_by_deadline)
+    /// went by before the value was ready
+    DeadlineExceeded,
+    /// The spawned task running a [`Cache::get_detached`](crate::Cache::get_detached)
+    /// load was dropped before it finished, for example because the runtime shut down
+    LoadCancelled,
+    /// The key's last load failed within the `failure_cooldown`, so the loader
+    /// wasn't called, see [`CacheBuilder::failure_cooldown`](crate::CacheBuilder::failure_cooldown)
+    InCooldown {
+        /// How long until the key may be loaded again
+        retry_after: Duration,
+    },
+}
+
+impl CacheError {
+    /// Moves a loader error into a [`SharedError`] so it can be handed out more than once
+    pub(crate) fn into_shared(self) -> Self {
+        match self {
+            CacheError::Load(error) => CacheError::Load(Box::new(SharedError::share(error))),
+            CacheError::InvalidKey(error) => {
+                CacheError::InvalidKey(Box::new(SharedError::share(error)))
+            }
+            CacheError::BatchFailed { identifier, source } => CacheError::BatchFailed {
+                identifier,
+                source: Box::new(source.into_shared()),
+            },
+            other => other,
+        }
+    }
+
+    /// Copies an error that went through [`into_shared`](Self::into_shared)
+    pub(crate) fn clone_shared(&self) -> Self {
+        match self {
+            CacheError::Load(error) => CacheError::Load(Box::new(
+                error
+                    .downcast_ref::<SharedError>()
+                    .expect("only shared load errors are cloned")
+                    .clone(),
+            )),
+            CacheError::LoadTimeout(limit) => CacheError::LoadTimeout(*limit),
+            CacheError::NotCached => CacheError::NotCached,
+            CacheError::LoaderPanicked(message) => CacheError::LoaderPanicked(message.clone()),
+            CacheError::LockPoisoned => CacheError::LockPoisoned,
+            CacheError::WaitTimeout(patience) => CacheError::WaitTimeout(*patience),
+            CacheError::InvalidKey(error) => CacheError::InvalidKey(Box::new(
+                error
+                    .downcast_ref::<SharedError>()
+                    .expect("only shared key errors are cloned")
+                    .clone(),
+            )),
+            CacheError::KeyCollision(identifier) => CacheError::KeyCollision(identifier.clone()),
+            CacheError::BatchFailed { identifier, source } => CacheError::BatchFailed {
+                identifier: identifier.clone(),
+                source: Box::new(source.clone_shared()),
+            },
+            CacheError::CircuitOpen => CacheError::CircuitOpen,
+            CacheError::ShuttingDown => CacheError::ShuttingDown,
+            CacheError::DeadlineExceeded => CacheError::DeadlineExceeded,
+            CacheError::LoadCancelled => CacheError::LoadCancelled,
+            CacheError::InCooldown { retry_after } => CacheError::InCooldown {
+                retry_after: *retry_after,
+            },
+        }
+    }
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Load(error) => fmt::Display::fmt(error, f),
+            CacheError::LoadTimeout(limit) => write!(f, "loader timed out after {:?}", limit),
+            CacheError::NotCached => write!(f, "no live entry cached for key"),
+            CacheError::LoaderPanicked(message) => write!(f, "loader panicked: {}", message),
+            CacheError::LockPoisoned => write!(f, "cache lock poisoned by an earlier panic"),
+            CacheError::WaitTimeout(patience) => {
+                write!(f, "gave up waiting on a running load after {:?}", patience)
+            }
+            CacheError::InvalidKey(error) => write!(f, "invalid key: {}", error),
+            CacheError::KeyCollision(identifier) => {
+                write!(f, "another key already maps to identifier {:?}", identifier)
+            }
+            CacheError::BatchFailed { identifier, source } => {
+                write!(f, "loading {:?} failed: {}", identifier, source)
+            }
+            CacheError::CircuitOpen => write!(f, "circuit breaker open, loader not called"),
+            CacheError::ShuttingDown => write!(f, "cache is shutting down"),
+            CacheError::DeadlineExceeded => write!(f, "deadline passed before the value was ready"),
+            CacheError::LoadCancelled => write!(f, "load task was dropped before it finished"),
+            CacheError::InCooldown { retry_after } => {
+                write!(f, "last load failed, retrying in {:?}", retry_after)
+            }
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CacheError::Load(error) => error.source(),
+            CacheError::InvalidKey(error) => Some(error.as_ref()),
+            CacheError::BatchFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<BoxError> for CacheError {
+    fn from(error: BoxError) -> Self {
+        CacheError::Load(error)
+    }
+}
+
+/// A loader error shared between several callers
+///
+/// Loader errors are boxed trait objects and can't be cloned, so when one error
+/// has to be handed out more than once (for example from the negative cache)
+/// it's moved into an `Arc` and every caller receives a `SharedError` pointing
+/// at the same original error. Display and `source` are forwarded, and the
+/// original error can be downcast through [`SharedError::inner`].
+#[derive(Clone)]
+pub struct SharedError(Arc<dyn Error + Send + Sync>);
+
+impl SharedError {
+    /// Wraps a loader error for sharing, unless it already is a `SharedError`
+    pub(crate) fn share(error: BoxError) -> Self {
+        match error.downcast::<SharedError>() {
+            Ok(shared) => *shared,
+            Err(error) => SharedError::from(error),
+        }
+    }
+
+    /// Returns the original loader error
+    pub fn inner(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.0
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for SharedError {
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        Self(Arc::from(error))
+    }
+}
+
+impl fmt::Debug for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl Error for SharedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+/// Why an entry left the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvictionReason {
+    /// The entry's expiry passed
+    Expired,
+    /// The entry was evicted to keep the cache within its capacity
+    Capacity,
+    /// The entry was removed by `delete` or `delete_all`
+    Manual,
+    /// The entry was overwritten by a newer value for the same key
+    Replaced,
+}
+
+/// Whether a single lookup was served from the cache, see [`get_instrumented`](crate::Cache::get_instrumented)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheOutcome {
+    /// A live entry was already cached
+    Hit,
+    /// The loader was called, taking `load_duration` including any retries
+    Miss { load_duration: Duration },
+}
+
+/// How fresh a value returned by [`get_with_source`](crate::Cache::get_with_source) is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueSource {
+    /// The value was just loaded, by this call or one it waited on
+    Fresh,
+    /// A live entry was already cached
+    Cached,
+    /// A cached entry past its soft expiry or refresh-ahead point, with a
+    /// background reload under way
+    StaleRevalidating,
+    /// The reload failed and an expired entry was served under
+    /// [`ReloadErrorPolicy::StaleFallback`](crate::ReloadErrorPolicy::StaleFallback)
+    StaleFallbackOnError,
+    /// The value wasn't cached locally and was found in the
+    /// [`BackingStore`](crate::BackingStore), by this call or one it waited on
+    Backing,
+}
+
+/// A change to the cache's contents, as seen by [`subscribe`](crate::Cache::subscribe)
+///
+/// Each event carries the mapped identifier of the affected entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CacheEvent {
+    /// A value was inserted or loaded
+    Inserted(String),
+    /// A value was removed manually, by capacity, or by being replaced
+    Evicted(String),
+    /// An expired value was removed
+    Expired(String),
+}
@@ -0,0 +1,136 @@
+use crate::error::CacheError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// What a finished load hands to the callers that joined it
+type Landing<T> = Option<Result<T, CacheError>>;
+
+/// Loads currently running, so concurrent misses for a key share one loader call
+///
+/// The first caller to miss becomes the leader and runs the loader; anyone
+/// missing on the same identifier meanwhile waits for the leader's result.
+pub(crate) struct InFlight<T> {
+    loads: Mutex<HashMap<String, watch::Receiver<Landing<T>>>>,
+}
+
+/// A caller's role in the load for an identifier
+pub(crate) enum Flight<'a, T> {
+    /// No load was running; the caller runs it and must [`land`](LoadGuard::land) the result
+    Leader(LoadGuard<'a, T>),
+    /// Another caller's load is running
+    Follower(Waiter<T>),
+}
+
+impl<T: Clone> InFlight<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            loads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns a waiter for the load running for `identifier`, if there is one
+    pub(crate) fn running(&self, identifier: &str) -> Option<Waiter<T>> {
+        let loads = self.loads.lock().ok()?;
+        loads.get(identifier).cloned().map(Waiter)
+    }
+
+    /// Waits until every load running now has landed or been dropped
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) async fn settled(&self) {
+        let running: Vec<_> = match self.loads.lock() {
+            Ok(loads) => loads.values().cloned().collect(),
+            Err(_) => return,
+        };
+        for mut landing in running {
+            let _ = landing.wait_for(Option::is_some).await;
+        }
+    }
+
+    /// Joins the running load for `identifier`, or registers a new one led by the caller
+    pub(crate) fn join(&self, identifier: &str) -> Flight<'_, T> {
+        let (sender, receiver) = watch::channel(None);
+        if let Ok(mut loads) = self.loads.lock() {
+            if let Some(running) = loads.get(identifier) {
+                return Flight::Follower(Waiter(running.clone()));
+            }
+            loads.insert(identifier.to_string(), receiver);
+        }
+        Flight::Leader(LoadGuard {
+            in_flight: self,
+            identifier: Some(identifier.to_string()),
+            sender,
+        })
+    }
+}
+
+/// A caller waiting on another caller's load
+pub(crate) struct Waiter<T>(watch::Receiver<Landing<T>>);
+
+impl<T: Clone> Waiter<T> {
+    /// Waits for the leader's result
+    ///
+    /// Returns `None` if the leader was dropped before finishing, in which
+    /// case the caller should try to load again.
+    pub(crate) async fn landed(mut self) -> Landing<T> {
+        let landed = self.0.wait_for(Option::is_some).await.ok()?;
+        match &*landed {
+            Some(Ok(item)) => Some(Ok(item.clone())),
+            Some(Err(error)) => Some(Err(error.clone_shared())),
+            None => None,
+        }
+    }
+}
+
+/// The leader's claim on a load
+///
+/// Dropping the guard unregisters the load, even when the leader's future is
+/// cancelled mid-load, so waiting callers wake up and retry instead of hanging.
+pub(crate) struct LoadGuard<'a, T> {
+    in_flight: &'a InFlight<T>,
+    /// `None` once the load is unregistered
+    identifier: Option<String>,
+    sender: watch::Sender<Landing<T>>,
+}
+
+impl<T> LoadGuard<'_, T> {
+    /// Whether any other caller is waiting on this load
+    pub(crate) fn has_followers(&self) -> bool {
+        self.sender.receiver_count() > 1
+    }
+
+    /// Hands the result to every waiting caller
+    ///
+    /// An error must already have gone through [`CacheError::into_shared`].
+    pub(crate) fn land(self, result: Result<T, CacheError>) {
+        self.sender.send_replace(Some(result));
+    }
+
+    /// Hands a loaded value to every waiting caller, cloning it only if there are any
+    ///
+    /// The load is unregistered first, so nobody can join between counting the
+    /// callers and dropping the guard.
+    pub(crate) fn land_value(mut self, value: &T)
+    where
+        T: Clone,
+    {
+        self.unregister();
+        if self.sender.receiver_count() > 0 {
+            self.sender.send_replace(Some(Ok(value.clone())));
+        }
+    }
+
+    fn unregister(&mut self) {
+        if let Some(identifier) = self.identifier.take()
+            && let Ok(mut loads) = self.in_flight.loads.lock()
+        {
+            loads.remove(&identifier);
+        }
+    }
+}
+
+impl<T> Drop for LoadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
@@ -0,0 +1,76 @@
+use std::future::{Future, poll_fn};
+use std::pin::Pin;
+use std::task::Poll;
+
+/// Runs futures concurrently on the current task, returning their outputs in order
+///
+/// Nothing is spawned, so this works without a runtime; every pending future
+/// is polled again whenever any of them wakes the task.
+pub(crate) async fn join_all<Fut: Future>(
+    futures: impl IntoIterator<Item = Fut>,
+) -> Vec<Fut::Output> {
+    let mut pending: Vec<Option<Pin<Box<Fut>>>> = futures
+        .into_iter()
+        .map(|future| Some(Box::pin(future)))
+        .collect();
+    let mut outputs: Vec<Option<Fut::Output>> = pending.iter().map(|_| None).collect();
+
+    poll_fn(|cx| {
+        let mut done = true;
+        for (slot, output) in pending.iter_mut().zip(outputs.iter_mut()) {
+            let Some(future) = slot else {
+                continue;
+            };
+            match future.as_mut().poll(cx) {
+                Poll::Ready(value) => {
+                    *output = Some(value);
+                    *slot = None;
+                }
+                Poll::Pending => done = false,
+            }
+        }
+        if done { Poll::Ready(()) } else { Poll::Pending }
+    })
+    .await;
+
+    outputs.into_iter().flatten().collect()
+}
+
+/// Like [`join_all`], but stops at the first error, dropping the futures still running
+pub(crate) async fn try_join_all<T, E, Fut>(
+    futures: impl IntoIterator<Item = Fut>,
+) -> Result<Vec<T>, E>
+where
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut pending: Vec<Option<Pin<Box<Fut>>>> = futures
+        .into_iter()
+        .map(|future| Some(Box::pin(future)))
+        .collect();
+    let mut outputs: Vec<Option<T>> = pending.iter().map(|_| None).collect();
+
+    poll_fn(|cx| {
+        let mut done = true;
+        for (slot, output) in pending.iter_mut().zip(outputs.iter_mut()) {
+            let Some(future) = slot else {
+                continue;
+            };
+            match future.as_mut().poll(cx) {
+                Poll::Ready(Ok(value)) => {
+                    *output = Some(value);
+                    *slot = None;
+                }
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => done = false,
+            }
+        }
+        if done {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await?;
+
+    Ok(outputs.into_iter().flatten().collect())
+}
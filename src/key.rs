@@ -0,0 +1,129 @@
+//! Mapping keys to the identifiers their entries are stored under
+
+use crate::cache::BoxError;
+use crate::error::CacheError;
+use crate::store::StoredKey;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Maps a key to the identifier its entry is stored under
+///
+/// Every `Fn(&K) -> String` is a key mapper, so plain closures work as is.
+/// Wrap a mapper that can reject keys in [`TryKeyMapper`].
+pub trait KeyMapper<K> {
+    /// Maps `key`, or rejects it with [`CacheError::InvalidKey`]
+    fn map_key(&self, key: &K) -> Result<String, CacheError>;
+}
+
+impl<K, F> KeyMapper<K> for F
+where
+    F: Fn(&K) -> String,
+{
+    fn map_key(&self, key: &K) -> Result<String, CacheError> {
+        Ok(self(key))
+    }
+}
+
+/// A key mapper that validates keys and can reject them
+///
+/// Lookups with a rejected key fail with [`CacheError::InvalidKey`] before the
+/// loader or the stored entries are touched. Methods that can't fail treat a
+/// rejected key as absent: `insert` and `delete` do nothing, and `try_peek`
+/// returns `None`.
+///
+/// ```rust
+/// use cache_rs::{Cache, Expiring, TryKeyMapper};
+///
+/// let cache = Cache::with_key_validation(
+///     |key: String| Box::pin(async move { Ok(Expiring::never(key.len())) }),
+///     |key: &String| {
+///         if key.is_empty() {
+///             return Err("empty key");
+///         }
+///         Ok(key.clone())
+///     },
+/// );
+/// # let _: &Cache<_, _, _, TryKeyMapper<_>> = &cache;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TryKeyMapper<M>(pub M);
+
+impl<K, M, E> KeyMapper<K> for TryKeyMapper<M>
+where
+    M: Fn(&K) -> Result<String, E>,
+    E: Into<BoxError>,
+{
+    fn map_key(&self, key: &K) -> Result<String, CacheError> {
+        (self.0)(key).map_err(|error| CacheError::InvalidKey(error.into()))
+    }
+}
+
+/// Remembers the first key mapped to each identifier, to catch key mappers
+/// that send two different keys to the same identifier
+pub(crate) struct CollisionCheck<K> {
+    keys: RwLock<HashMap<String, K>>,
+    eq: fn(&K, &K) -> bool,
+}
+
+impl<K: Clone> CollisionCheck<K> {
+    pub(crate) fn new() -> Self
+    where
+        K: PartialEq,
+    {
+        Self {
+            keys: RwLock::new(HashMap::new()),
+            eq: K::eq,
+        }
+    }
+
+    /// Fails with [`CacheError::KeyCollision`] if another key already claimed `identifier`
+    pub(crate) fn check(&self, identifier: &str, key: &K) -> Result<(), CacheError> {
+        let claimed = match self.keys.read() {
+            Ok(keys) => keys.get(identifier).map(|seen| (self.eq)(seen, key)),
+            Err(_) => return Err(CacheError::LockPoisoned),
+        };
+        let same = match claimed {
+            Some(same) => same,
+            None => {
+                let Ok(mut keys) = self.keys.write() else {
+                    return Err(CacheError::LockPoisoned);
+                };
+                let seen = keys
+                    .entry(identifier.to_string())
+                    .or_insert_with(|| key.clone());
+                (self.eq)(seen, key)
+            }
+        };
+        if same {
+            Ok(())
+        } else {
+            Err(CacheError::KeyCollision(identifier.to_string()))
+        }
+    }
+}
+
+/// Turns keys into the [`StoredKey`]s entries keep, and back
+///
+/// Built where `K: 'static` is known, so the rest of the cache can remember
+/// and recover keys without that bound.
+pub(crate) struct KeyCodec<K> {
+    pub(crate) erase: fn(&K) -> StoredKey,
+    pub(crate) recover: fn(&StoredKey) -> Option<K>,
+}
+
+impl<K: Clone + Send + Sync + 'static> KeyCodec<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            erase: |key| Arc::new(key.clone()),
+            recover: |stored| stored.downcast_ref::<K>().cloned(),
+        }
+    }
+}
+
+impl<K> Clone for KeyCodec<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for KeyCodec<K> {}
@@ -0,0 +1,98 @@
+use crate::cache::{Expiring, LoadFuture};
+use crate::error::CacheError;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::RwLock;
+
+/// A cache keyed directly by `K` instead of a mapped `String` identifier
+///
+/// When keys are already `Hash + Eq` (integers, tuples, small structs) this
+/// skips the key mapper entirely, so lookups never format or allocate a string.
+/// Use [`Cache`](crate::Cache) when keys need normalizing before they're stored.
+pub struct KeyedCache<K, V, F>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    F: Fn(K) -> LoadFuture<V>,
+{
+    map: RwLock<HashMap<K, Expiring<V>>>,
+    load: F,
+}
+
+impl<K, V, F> KeyedCache<K, V, F>
+where
+    K: Hash + Eq + Clone + Send + Sync,
+    V: Clone + Send + Sync,
+    F: Fn(K) -> LoadFuture<V>,
+{
+    /// Creates a new cache with the given loader
+    pub fn new(load: F) -> Self {
+        Self {
+            map: RwLock::new(HashMap::new()),
+            load,
+        }
+    }
+
+    /// Gets a value from the cache, loading it if necessary or expired
+    ///
+    /// If the loader returns a value that has already expired, such as one with
+    /// a zero TTL, it's returned to the caller but not cached.
+    pub async fn get(&self, key: K) -> Result<V, CacheError> {
+        let expiring = self.get_with_expiry(key).await?;
+        Ok(expiring.value)
+    }
+
+    /// Gets a value with its expiration information
+    pub async fn get_with_expiry(&self, key: K) -> Result<Expiring<V>, CacheError> {
+        if let Ok(map) = self.map.read()
+            && let Some(item) = map.get(&key)
+            && !item.is_expired()
+        {
+            return Ok(item.clone());
+        }
+
+        let item = (self.load)(key.clone()).await?;
+        if !item.remaining_ttl().is_zero()
+            && let Ok(mut map) = self.map.write()
+        {
+            map.insert(key, item.clone());
+        }
+        Ok(item)
+    }
+
+    /// Inserts an item into the cache, replacing any existing value for the key
+    pub fn insert(&self, key: K, item: Expiring<V>) {
+        if let Ok(mut map) = self.map.write() {
+            map.insert(key, item);
+        }
+    }
+
+    /// Deletes an item from the cache
+    pub fn delete(&self, key: &K) {
+        if let Ok(mut map) = self.map.write() {
+            map.remove(key);
+        }
+    }
+
+    /// Clears all items from the cache
+    pub fn delete_all(&self) {
+        if let Ok(mut map) = self.map.write() {
+            map.clear();
+        }
+    }
+
+    /// Removes all expired items from the cache, returning how many were removed
+    pub fn purge_expired(&self) -> usize {
+        let Ok(mut map) = self.map.write() else {
+            return 0;
+        };
+        let before = map.len();
+        map.retain(|_, item| !item.is_expired());
+        before - map.len()
+    }
+
+    /// Gets the current size of the cache
+    pub fn size(&self) -> usize {
+        self.map.read().map(|map| map.len()).unwrap_or(0)
+    }
+}
@@ -6,9 +6,29 @@
 //!
 //! - Generic key-value caching with custom types
 //! - Automatic expiration handling
-//! - Async support with configurable loaders
-//! - Thread-safe operations
-//! - Customizable key mapping
+//! - Optional negative caching of loader errors
+//! - Async support with configurable loaders, timeouts and retries
+//! - Thread-safe operations with sharded storage
+//! - Concurrent misses for the same key share a single loader call
+//! - Customizable key mapping, or direct `Hash + Eq` keys with [`KeyedCache`]
+//! - [`LocalCache`] for `!Send` loaders on single-threaded executors
+//! - [`WeakCache`] for memoizing shared objects without keeping them alive
+//! - Optional LRU capacity limit and sliding expiration
+//! - Eviction callbacks for releasing resources held by cached values
+//! - Broadcast stream of insert and eviction events
+//! - Dump and restore for warm restarts (serializable with the `serde` feature)
+//! - Optional [`BackingStore`] as a slower second level between the cache and its loader
+//!
+//! ## Feature Flags
+//!
+//! - `tokio` (default) - load timeouts, retry backoff and the janitor use Tokio's
+//!   timers and `tokio::spawn`, so they must run inside a Tokio runtime
+//! - `async-std` - the same features on async-std, used when `tokio` is disabled
+//! - `serde` - `Serialize`/`Deserialize` for [`CacheSnapshot`] and [`Expiring`]
+//! - `tower` - `CacheLayer` and `CacheService` for memoizing a `tower` service's responses
+//!
+//! With neither runtime feature enabled, the core `get`/`insert`/`delete` path
+//! still works on any executor; only the timer-based options are unavailable.
 //!
 //! ## Quick Start
 //!
@@ -33,6 +53,50 @@
 //! # }
 //! ```
 
+pub mod backing;
+pub mod breaker;
+pub mod builder;
 pub mod cache;
+mod clock;
+pub mod entry;
+pub mod error;
+pub mod events;
+mod flight;
+mod join;
+pub mod key;
+pub mod keyed;
+pub mod local;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub mod retry;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod runtime;
+pub mod snapshot;
+pub mod stats;
+mod store;
+#[cfg(feature = "tower")]
+pub mod tower;
+mod unwind;
+pub mod weak;
 
-pub use cache::{Cache, CacheConfig, Expiring};
+pub use backing::{BackingFuture, BackingStore, MemoryBackingStore, NoBackingStore};
+pub use breaker::CircuitBreaker;
+pub use builder::CacheBuilder;
+pub use cache::{
+    AdaptiveTtl, BatchLoadFuture, BatchLoader, BoxError, ByExpiry, Cache, CacheConfig,
+    CacheOptions, DEFAULT_EVENT_CAPACITY, DEFAULT_SHARD_COUNT, EvictionCallback, EvictionPolicy,
+    Expiring, LoadFuture, MetaLoadFuture, OptionalLoadFuture, PoisonPolicy, ReloadErrorPolicy,
+    Transform, Weigher,
+};
+pub use entry::EntryHandle;
+pub use error::{CacheError, SharedError};
+pub use events::{CacheEvent, CacheOutcome, EvictionReason, ValueSource};
+pub use key::{KeyMapper, TryKeyMapper};
+pub use keyed::KeyedCache;
+pub use local::{LocalCache, LocalLoadFuture};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use retry::RetryPolicy;
+pub use snapshot::{CacheSnapshot, SnapshotEntry};
+pub use stats::CacheStats;
+#[cfg(feature = "tower")]
+pub use tower::{CacheLayer, CacheService};
+pub use weak::WeakCache;
@@ -0,0 +1,102 @@
+use crate::cache::{BoxError, Expiring};
+use crate::error::CacheError;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+/// Future returned by a [`LocalCache`] loader, which doesn't need to be `Send`
+pub type LocalLoadFuture<V> = Pin<Box<dyn Future<Output = Result<Expiring<V>, BoxError>>>>;
+
+/// A single-threaded cache whose loaders and values don't need to be `Send`
+///
+/// Meant for executors that keep every task on one thread, such as Tokio's
+/// `LocalSet`, where loaders may hold `Rc`s or other `!Send` resources. Keys
+/// are mapped to identifiers like in [`Cache`](crate::Cache), but entries live
+/// in a `RefCell` instead of sharded locks, and there's no eviction, refresh
+/// or load coalescing. No borrow is held across an `.await`, so tasks on the
+/// same thread can share the cache through an `Rc`.
+pub struct LocalCache<K, V, F, G>
+where
+    V: Clone,
+    F: Fn(K) -> LocalLoadFuture<V>,
+    G: Fn(&K) -> String,
+{
+    map: RefCell<HashMap<String, Expiring<V>>>,
+    load: F,
+    get_key_for_map: G,
+    _phantom: PhantomData<K>,
+}
+
+impl<K, V, F, G> LocalCache<K, V, F, G>
+where
+    V: Clone,
+    F: Fn(K) -> LocalLoadFuture<V>,
+    G: Fn(&K) -> String,
+{
+    /// Creates a new cache with the given loader and key mapper functions
+    pub fn new(load: F, get_key_for_map: G) -> Self {
+        Self {
+            map: RefCell::new(HashMap::new()),
+            load,
+            get_key_for_map,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Gets a value from the cache, loading it if necessary or expired
+    ///
+    /// Like [`Cache::get`](crate::Cache::get), a loaded value that has already
+    /// expired is returned but not cached.
+    pub async fn get(&self, key: K) -> Result<V, CacheError> {
+        let expiring = self.get_with_expiry(key).await?;
+        Ok(expiring.value)
+    }
+
+    /// Gets a value with its expiration information
+    pub async fn get_with_expiry(&self, key: K) -> Result<Expiring<V>, CacheError> {
+        let identifier = (self.get_key_for_map)(&key);
+        if let Some(item) = self.map.borrow().get(&identifier)
+            && !item.is_expired()
+        {
+            return Ok(item.clone());
+        }
+
+        let item = (self.load)(key).await?;
+        if !item.remaining_ttl().is_zero() {
+            self.map.borrow_mut().insert(identifier, item.clone());
+        }
+        Ok(item)
+    }
+
+    /// Inserts an item into the cache, replacing any existing value for the key
+    pub fn insert(&self, key: K, item: Expiring<V>) {
+        let identifier = (self.get_key_for_map)(&key);
+        self.map.borrow_mut().insert(identifier, item);
+    }
+
+    /// Deletes an item from the cache
+    pub fn delete(&self, key: &K) {
+        let identifier = (self.get_key_for_map)(key);
+        self.map.borrow_mut().remove(&identifier);
+    }
+
+    /// Clears all items from the cache
+    pub fn delete_all(&self) {
+        self.map.borrow_mut().clear();
+    }
+
+    /// Removes all expired items from the cache, returning how many were removed
+    pub fn purge_expired(&self) -> usize {
+        let mut map = self.map.borrow_mut();
+        let before = map.len();
+        map.retain(|_, item| !item.is_expired());
+        before - map.len()
+    }
+
+    /// Gets the current size of the cache
+    pub fn size(&self) -> usize {
+        self.map.borrow().len()
+    }
+}
@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Predicate deciding whether a loader error is worth retrying
+pub type RetryPredicate = Arc<dyn Fn(&(dyn Error + Send + Sync + 'static)) -> bool + Send + Sync>;
+
+/// How failed loads are retried before the error reaches the caller
+///
+/// The loader is called up to `max_attempts` times in total, waiting `backoff`
+/// between attempts (doubling after each attempt when exponential). Only the
+/// error from the final attempt is returned.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub backoff: Duration,
+    pub exponential: bool,
+    is_retryable: Option<RetryPredicate>,
+}
+
+impl RetryPolicy {
+    /// Creates a policy with a fixed delay between attempts that retries every error
+    pub fn new(max_attempts: usize, backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff,
+            exponential: false,
+            is_retryable: None,
+        }
+    }
+
+    /// Doubles the delay after each failed attempt
+    pub fn exponential(mut self) -> Self {
+        self.exponential = true;
+        self
+    }
+
+    /// Only retries errors for which `is_retryable` returns true
+    pub fn retry_if(
+        mut self,
+        is_retryable: impl Fn(&(dyn Error + Send + Sync + 'static)) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_retryable = Some(Arc::new(is_retryable));
+        self
+    }
+
+    /// Returns the delay before the attempt following `attempt` (counting from 1)
+    pub(crate) fn delay_after(&self, attempt: usize) -> Duration {
+        if self.exponential {
+            let factor = 2u32.saturating_pow(attempt.saturating_sub(1) as u32);
+            self.backoff.saturating_mul(factor)
+        } else {
+            self.backoff
+        }
+    }
+
+    /// Checks whether another attempt should follow a failed `attempt`
+    pub(crate) fn should_retry(
+        &self,
+        attempt: usize,
+        error: &(dyn Error + Send + Sync + 'static),
+    ) -> bool {
+        attempt < self.max_attempts
+            && self
+                .is_retryable
+                .as_ref()
+                .is_none_or(|is_retryable| is_retryable(error))
+    }
+}
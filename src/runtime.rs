@@ -0,0 +1,36 @@
+//! Timer and task-spawning glue for the supported async runtimes
+//!
+//! The core get/insert/delete path doesn't need a runtime. Features that wait or
+//! run in the background (load timeouts, retry backoff, the janitor) go through
+//! the functions here, which use Tokio when the `tokio` feature is enabled and
+//! async-std otherwise.
+
+use std::future::Future;
+use std::time::Duration;
+
+/// Waits for `duration`
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+
+    #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+    async_std::task::sleep(duration).await;
+}
+
+/// Runs `future` to completion, or returns `None` if `duration` passes first
+pub(crate) async fn timeout<T>(duration: Duration, future: impl Future<Output = T>) -> Option<T> {
+    #[cfg(feature = "tokio")]
+    return tokio::time::timeout(duration, future).await.ok();
+
+    #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+    return async_std::future::timeout(duration, future).await.ok();
+}
+
+/// Runs `future` in the background
+pub(crate) fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+    #[cfg(feature = "tokio")]
+    tokio::spawn(future);
+
+    #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+    async_std::task::spawn(future);
+}
@@ -0,0 +1,49 @@
+use crate::cache::Expiring;
+use std::time::SystemTime;
+
+/// An owned copy of a cache's live entries, suitable for persisting
+///
+/// With the `serde` feature enabled this implements `Serialize` and
+/// `Deserialize`, so a cache can be written out with
+/// [`Cache::dump`](crate::Cache::dump) and warmed again on startup with
+/// [`Cache::restore`](crate::Cache::restore). Loaders and key mappers aren't
+/// part of the snapshot; the cache being restored into supplies its own.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheSnapshot<V> {
+    pub entries: Vec<SnapshotEntry<V>>,
+}
+
+/// A single entry in a [`CacheSnapshot`], keyed by its mapped identifier
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotEntry<V> {
+    pub identifier: String,
+    pub value: V,
+    pub expires_at: Option<SystemTime>,
+    /// Defaults to `None` when reading snapshots saved without it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub soft_expires_at: Option<SystemTime>,
+    /// When the entry was last read, or stored if it never was
+    ///
+    /// Informational only: [`Cache::restore`](crate::Cache::restore) ignores
+    /// it. Defaults to `None` when reading snapshots saved without it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub last_accessed: Option<SystemTime>,
+}
+
+impl<V> From<Vec<(String, Expiring<V>)>> for CacheSnapshot<V> {
+    fn from(entries: Vec<(String, Expiring<V>)>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|(identifier, item)| SnapshotEntry {
+                identifier,
+                soft_expires_at: item.soft_expires_at(),
+                value: item.value,
+                expires_at: item.expires_at,
+                last_accessed: None,
+            })
+            .collect();
+        Self { entries }
+    }
+}
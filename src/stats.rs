@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters describing how a cache's lookups have been served
+///
+/// Returned by [`Cache::stats`](crate::Cache::stats). Only lookups that may
+/// load count: `get` and the variants built on it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups served from a live entry
+    pub hits: u64,
+    /// Lookups that found no live entry, including coalesced ones
+    pub misses: u64,
+    /// Misses that waited on a load another caller had already started
+    ///
+    /// `misses - coalesced_loads` is how many loads were actually started.
+    pub coalesced_loads: u64,
+    /// Loads of a key whose previous entry had expired less than a second earlier
+    ///
+    /// A high count next to a low hit ratio suggests TTLs are too short for how
+    /// often keys are read. Only expired entries still stored are noticed, so
+    /// reloads after a purge or eviction don't count.
+    pub near_miss_reloads: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups that were hits, or `0.0` before any lookup
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+/// Running totals behind [`CacheStats`]
+#[derive(Default)]
+pub(crate) struct StatsCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced_loads: AtomicU64,
+    near_miss_reloads: AtomicU64,
+}
+
+impl StatsCounters {
+    pub(crate) fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_coalesced(&self) {
+        self.coalesced_loads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_near_miss(&self) {
+        self.near_miss_reloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            coalesced_loads: self.coalesced_loads.load(Ordering::Relaxed),
+            near_miss_reloads: self.near_miss_reloads.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Sub-buckets per power of two, bounding a bucket's width to a quarter of its start
+const SUB_BUCKETS: u64 = 4;
+const SUB_BUCKET_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+const LATENCY_BUCKETS: usize = (SUB_BUCKETS + (64 - SUB_BUCKET_BITS as u64) * SUB_BUCKETS) as usize;
+
+/// Log-linear histogram of loader call durations in microseconds
+///
+/// Recording is a single relaxed `fetch_add` on one bucket, so concurrent
+/// loads never wait on each other. Percentiles are reported as the upper
+/// bound of their bucket, which overestimates by at most 25%.
+pub(crate) struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..LATENCY_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.buckets[Self::bucket(micros)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The duration below which a fraction `p` of the recorded calls fell
+    ///
+    /// `p` is clamped to `0.0..=1.0`. `None` until a call has been recorded.
+    pub(crate) fn percentile(&self, p: f64) -> Option<Duration> {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((p.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = counts.iter().position(|&count| {
+            seen += count;
+            seen >= rank
+        })?;
+        Some(Duration::from_micros(Self::upper_bound(bucket)))
+    }
+
+    fn bucket(micros: u64) -> usize {
+        if micros < SUB_BUCKETS {
+            return micros as usize;
+        }
+        let exponent = 63 - micros.leading_zeros();
+        let shift = exponent - SUB_BUCKET_BITS;
+        let sub_bucket = (micros >> shift) & (SUB_BUCKETS - 1);
+        (SUB_BUCKETS + u64::from(shift) * SUB_BUCKETS + sub_bucket) as usize
+    }
+
+    fn upper_bound(bucket: usize) -> u64 {
+        let bucket = bucket as u64;
+        if bucket < SUB_BUCKETS {
+            return bucket;
+        }
+        let shift = (bucket - SUB_BUCKETS) / SUB_BUCKETS;
+        let sub_bucket = (bucket - SUB_BUCKETS) % SUB_BUCKETS;
+        let end = u128::from(SUB_BUCKETS + sub_bucket + 1) << shift;
+        u64::try_from(end - 1).unwrap_or(u64::MAX)
+    }
+}
@@ -0,0 +1,1453 @@
+//! Internal storage: sharded maps of entries plus their eviction bookkeeping
+
+use crate::breaker::Breaker;
+use crate::cache::{ByExpiry, CacheOptions, EvictionPolicy, Expiring, Hooks, PoisonPolicy};
+use crate::clock::{CoarseClock, nanos_since_epoch};
+use crate::events::{CacheEvent, EvictionReason};
+use crate::stats::LatencyHistogram;
+use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{Semaphore, SemaphorePermit, broadcast};
+
+/// Capacity assumed for the LFU aging window when only a weight limit is set
+const DEFAULT_LFU_CAPACITY: usize = 1_000;
+
+/// LFU frequencies are halved after this many hits per unit of capacity
+const LFU_AGING_FACTOR: u64 = 10;
+
+/// Stale expiry records a shard tolerates before rebuilding its expiry queue
+const EXPIRY_QUEUE_SLACK: usize = 64;
+
+/// A key kept alongside its entry, see [`Cache::remember_keys`](crate::Cache::remember_keys)
+///
+/// The store isn't generic over the key type, so the key is type-erased and
+/// downcast back when the keys are enumerated.
+pub(crate) type StoredKey = Arc<dyn Any + Send + Sync>;
+
+/// A cached item plus the bookkeeping needed for eviction and refreshing
+pub(crate) struct Entry<V> {
+    pub(crate) item: Expiring<V>,
+    /// The key the item was stored under, when keys are remembered
+    pub(crate) key: Option<StoredKey>,
+    pub(crate) last_access: AtomicU64,
+    /// Wall-clock time of the last hit, as nanoseconds since the Unix epoch
+    ///
+    /// Atomic so a hit can update it under the shard's read lock.
+    pub(crate) accessed_at: AtomicU64,
+    /// Hits since the entry was stored, halved periodically when using LFU
+    pub(crate) frequency: AtomicU64,
+    /// Hits on the identifier since it was first stored, kept when the entry is replaced
+    pub(crate) hit_count: AtomicU64,
+    pub(crate) weight: usize,
+    /// The TTL the item had when it was stored, `None` if it never expires
+    pub(crate) ttl: Option<Duration>,
+    pub(crate) created_at: SystemTime,
+    /// Tick at which the value was produced: when its load started, or when it
+    /// was inserted directly
+    pub(crate) version: u64,
+}
+
+impl<V> Entry<V> {
+    pub(crate) fn new(
+        item: Expiring<V>,
+        tick: u64,
+        weight: usize,
+        max_age: Option<Duration>,
+    ) -> Self {
+        let created_at = SystemTime::now();
+        let mut entry = Self {
+            item,
+            key: None,
+            last_access: AtomicU64::new(tick),
+            accessed_at: AtomicU64::new(nanos_since_epoch(created_at)),
+            frequency: AtomicU64::new(0),
+            hit_count: AtomicU64::new(0),
+            weight,
+            ttl: None,
+            created_at,
+            version: tick,
+        };
+        entry.set_expiry(entry.item.expires_at, max_age);
+        entry.ttl = entry.item.expires_at.map(|expires_at| {
+            expires_at
+                .duration_since(created_at)
+                .unwrap_or(Duration::ZERO)
+        });
+        entry
+    }
+
+    /// Sets the expiry, capped so the entry never outlives `max_age` from creation
+    pub(crate) fn set_expiry(&mut self, expires_at: Option<SystemTime>, max_age: Option<Duration>) {
+        let deadline = max_age.and_then(|max_age| self.created_at.checked_add(max_age));
+        self.item.expires_at = match (expires_at, deadline) {
+            (Some(expires_at), Some(deadline)) => Some(expires_at.min(deadline)),
+            (expires_at, deadline) => expires_at.or(deadline),
+        };
+    }
+
+    /// When the entry was last hit, or stored if it hasn't been hit yet
+    pub(crate) fn last_accessed(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_nanos(self.accessed_at.load(Ordering::Relaxed))
+    }
+
+    /// Checks whether at least `threshold` of the entry's original TTL has elapsed
+    pub(crate) fn is_due_for_refresh(&self, threshold: f64) -> bool {
+        let Some(ttl) = self.ttl else {
+            return false;
+        };
+        let remaining = self.item.remaining_ttl();
+        ttl.saturating_sub(remaining).as_secs_f64() >= ttl.as_secs_f64() * threshold
+    }
+}
+
+type ShardReadGuard<'a, V, S> = RwLockReadGuard<'a, Shard<V, S>>;
+type ShardWriteGuard<'a, V, S> = RwLockWriteGuard<'a, Shard<V, S>>;
+
+/// How an entry ranks for eviction, lowest first: whether it's live, whether it
+/// was just inserted, its usage, then its identifier
+type EvictionRank = ((bool, bool, (u64, u64)), String);
+
+/// An entry removed from the cache, waiting for its eviction to be announced
+pub(crate) type Evicted<V> = (String, Expiring<V>, EvictionReason);
+
+/// An item to store under an identifier, with the key to remember for it if any
+pub(crate) type NewItem<V> = (String, Option<StoredKey>, Expiring<V>);
+
+/// Where a write comes from, which decides what it may overwrite
+#[derive(Clone, Copy)]
+enum Origin {
+    /// Inserted by the caller, always stored
+    Direct,
+    /// Loaded on a miss by a loader called at tick `started`
+    Load { started: u64 },
+    /// Reloaded in the background by a loader called at tick `started`
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    Refresh { started: u64 },
+}
+
+/// One shard of the cache, tracking the total weight of its entries
+///
+/// All mutations go through these methods so the weight, the ordered index
+/// and the expiry queue stay in sync.
+pub(crate) struct Shard<V, S = RandomState> {
+    map: HashMap<String, Entry<V>, S>,
+    weight: usize,
+    /// Every identifier in `map` in sorted order, when range queries are enabled
+    index: Option<BTreeSet<String>>,
+    /// Identifiers by expiry, soonest first, so purging doesn't scan the shard
+    ///
+    /// Every entry that expires has a record no later than its expiry. Records
+    /// of removed entries and of expiries pushed out later go stale, and are
+    /// dropped or requeued when they come up.
+    expiries: BinaryHeap<Reverse<ByExpiry<String>>>,
+}
+
+impl<V, S: BuildHasher> Shard<V, S> {
+    pub(crate) fn with_capacity_and_hasher(capacity: usize, hasher: S, ordered: bool) -> Self {
+        Self {
+            map: HashMap::with_capacity_and_hasher(capacity, hasher),
+            weight: 0,
+            index: ordered.then(BTreeSet::new),
+            expiries: BinaryHeap::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub(crate) fn weight(&self) -> usize {
+        self.weight
+    }
+
+    pub(crate) fn get(&self, identifier: &str) -> Option<&Entry<V>> {
+        self.map.get(identifier)
+    }
+
+    /// Gets an entry for in-place changes that don't affect its weight
+    pub(crate) fn get_mut(&mut self, identifier: &str) -> Option<&mut Entry<V>> {
+        self.map.get_mut(identifier)
+    }
+
+    /// Applies `f` to a live entry's value, reweighing it if there's a weigher
+    pub(crate) fn update(
+        &mut self,
+        identifier: &str,
+        tick: u64,
+        now: SystemTime,
+        f: impl FnOnce(&mut V),
+        weigher: Option<&(dyn Fn(&V) -> usize + Send + Sync)>,
+    ) -> bool {
+        let Some(entry) = self
+            .map
+            .get_mut(identifier)
+            .filter(|entry| !entry.item.is_expired_at(now))
+        else {
+            return false;
+        };
+        f(&mut entry.item.value);
+        entry.version = tick;
+        entry.last_access.store(tick, Ordering::Relaxed);
+        if let Some(weigher) = weigher {
+            let weight = weigher(&entry.item.value);
+            self.weight = self.weight - entry.weight + weight;
+            entry.weight = weight;
+        }
+        true
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, &Entry<V>)> {
+        self.map.iter()
+    }
+
+    /// Identifiers within `range`, or starting with `prefix` if given, in any order
+    ///
+    /// With the ordered index this takes O(log n + k), otherwise it scans the shard.
+    pub(crate) fn identifiers_in<'a>(
+        &'a self,
+        range: (Bound<&'a str>, Bound<&'a str>),
+        prefix: Option<&'a str>,
+    ) -> Vec<&'a String> {
+        let matches = |identifier: &String| {
+            range.contains(identifier.as_str())
+                && prefix.is_none_or(|prefix| identifier.starts_with(prefix))
+        };
+        match &self.index {
+            Some(index) => {
+                let from = match prefix {
+                    Some(prefix) => (Bound::Included(prefix), Bound::Unbounded),
+                    None => range,
+                };
+                index
+                    .range::<str, _>(from)
+                    .take_while(|identifier| {
+                        prefix.is_none_or(|prefix| identifier.starts_with(prefix))
+                    })
+                    .filter(|identifier| matches(identifier))
+                    .collect()
+            }
+            None => self
+                .map
+                .keys()
+                .filter(|identifier| matches(identifier))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, identifier: String, entry: Entry<V>) -> Option<Entry<V>> {
+        self.weight += entry.weight;
+        if let Some(index) = &mut self.index
+            && !self.map.contains_key(&identifier)
+        {
+            index.insert(identifier.clone());
+        }
+        if let Some(expires_at) = entry.item.expires_at {
+            self.queue_expiry(identifier.clone(), expires_at);
+        }
+        let old = self.map.insert(identifier, entry);
+        if let Some(old) = &old {
+            self.weight -= old.weight;
+        }
+        old
+    }
+
+    pub(crate) fn remove(&mut self, identifier: &str) -> Option<Entry<V>> {
+        let old = self.map.remove(identifier);
+        if let Some(old) = &old {
+            self.weight -= old.weight;
+            if let Some(index) = &mut self.index {
+                index.remove(identifier);
+            }
+        }
+        old
+    }
+
+    pub(crate) fn drain(&mut self) -> Vec<(String, Entry<V>)> {
+        self.weight = 0;
+        if let Some(index) = &mut self.index {
+            index.clear();
+        }
+        self.expiries.clear();
+        self.map.drain().collect()
+    }
+
+    /// Moves an entry's expiry to `expires_at`, capped by `max_age`
+    pub(crate) fn set_expiry(
+        &mut self,
+        identifier: &str,
+        expires_at: Option<SystemTime>,
+        max_age: Option<Duration>,
+    ) {
+        let Some(entry) = self.map.get_mut(identifier) else {
+            return;
+        };
+        entry.set_expiry(expires_at, max_age);
+        if let Some(expires_at) = entry.item.expires_at {
+            self.queue_expiry(identifier.to_string(), expires_at);
+        }
+    }
+
+    /// Removes and returns every entry that expired by `now`, soonest first
+    ///
+    /// Only looks at the entries due by `now`, not the whole shard.
+    pub(crate) fn extract_expired(&mut self, now: SystemTime) -> Vec<(String, Entry<V>)> {
+        let mut extracted = Vec::new();
+        while let Some(Reverse(ByExpiry(next))) = self.expiries.peek()
+            && next.expires_at.is_some_and(|expires_at| expires_at < now)
+        {
+            let Some(Reverse(ByExpiry(record))) = self.expiries.pop() else {
+                break;
+            };
+            let identifier = record.value;
+            let Some(entry) = self.map.get(&identifier) else {
+                continue;
+            };
+            if entry.item.is_expired_at(now) {
+                if let Some(entry) = self.remove(&identifier) {
+                    extracted.push((identifier, entry));
+                }
+            } else if let Some(expires_at) = entry.item.expires_at {
+                // The expiry was pushed out since this record was queued
+                self.expiries
+                    .push(Reverse(ByExpiry(Expiring::new(identifier, expires_at))));
+            }
+        }
+        extracted
+    }
+
+    /// Queues a record of an entry's expiry, rebuilding the queue once too many are stale
+    fn queue_expiry(&mut self, identifier: String, expires_at: SystemTime) {
+        self.expiries
+            .push(Reverse(ByExpiry(Expiring::new(identifier, expires_at))));
+        if self.expiries.len() > 2 * self.map.len() + EXPIRY_QUEUE_SLACK {
+            self.expiries = self
+                .map
+                .iter()
+                .filter_map(|(identifier, entry)| {
+                    let expires_at = entry.item.expires_at?;
+                    Some(Reverse(ByExpiry(Expiring::new(
+                        identifier.clone(),
+                        expires_at,
+                    ))))
+                })
+                .collect();
+        }
+    }
+
+    /// Removes the entries `keep` rejects, letting it modify the ones it keeps
+    ///
+    /// Kept entries are reweighed and their expiry is capped by `max_age` again.
+    pub(crate) fn retain(
+        &mut self,
+        mut keep: impl FnMut(&str, &mut Expiring<V>) -> bool,
+        max_age: Option<Duration>,
+        weigher: Option<&(dyn Fn(&V) -> usize + Send + Sync)>,
+    ) -> Vec<(String, Entry<V>)> {
+        let mut weight = self.weight;
+        let mut moved = Vec::new();
+        let extracted: Vec<_> = self
+            .map
+            .extract_if(|identifier, entry| {
+                let expires_at = entry.item.expires_at;
+                if !keep(identifier, &mut entry.item) {
+                    return true;
+                }
+                entry.set_expiry(entry.item.expires_at, max_age);
+                if let Some(moved_to) = entry.item.expires_at
+                    && expires_at != Some(moved_to)
+                {
+                    moved.push((identifier.to_string(), moved_to));
+                }
+                if let Some(weigher) = weigher {
+                    let reweighed = weigher(&entry.item.value);
+                    weight = weight - entry.weight + reweighed;
+                    entry.weight = reweighed;
+                }
+                false
+            })
+            .collect();
+        for (identifier, entry) in &extracted {
+            weight -= entry.weight;
+            if let Some(index) = &mut self.index {
+                index.remove(identifier);
+            }
+        }
+        self.weight = weight;
+        for (identifier, expires_at) in moved {
+            self.queue_expiry(identifier, expires_at);
+        }
+        extracted
+    }
+
+    /// Releases spare capacity left behind by removed entries
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+}
+
+/// Everything a cache stores, shared with the cache's background tasks
+///
+/// Loaders and key mappers stay on the [`Cache`](crate::Cache) itself; this
+/// holds the entries and the settings and hooks needed to insert and evict them,
+/// so a background refresh can write results back after the `get` that started
+/// it has returned.
+pub(crate) struct Store<V, S = RandomState> {
+    shards: Vec<RwLock<Shard<V, S>>>,
+    hasher: S,
+    pub(crate) options: CacheOptions,
+    pub(crate) hooks: Hooks<V>,
+    events: broadcast::Sender<CacheEvent>,
+    tick: AtomicU64,
+    /// Tick of the last clear; loads started before it aren't stored
+    cleared_at: AtomicU64,
+    hits: AtomicU64,
+    rng: AtomicU64,
+    eviction: Mutex<()>,
+    load_permits: Option<Semaphore>,
+    /// Set by shutdown, after which misses no longer load
+    closed: AtomicBool,
+    pub(crate) breaker: Option<Breaker>,
+    pub(crate) load_latency: LatencyHistogram,
+    clock: Option<Arc<CoarseClock>>,
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    refreshing: Mutex<HashSet<String>>,
+}
+
+impl<V, S> Store<V, S>
+where
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    pub(crate) fn new(mut options: CacheOptions, hooks: Hooks<V>, hasher: S) -> Self {
+        // A threshold above 1.0 only refreshes at expiry, and one that isn't
+        // positive (or is NaN) would refresh on every hit, so it's turned off
+        options.refresh_ahead = options
+            .refresh_ahead
+            .filter(|threshold| *threshold > 0.0)
+            .map(|threshold| threshold.min(1.0));
+        let shard_count = options.shard_count.max(1);
+        let shard_capacity = options.initial_capacity.div_ceil(shard_count);
+        Self {
+            shards: (0..shard_count)
+                .map(|_| {
+                    RwLock::new(Shard::with_capacity_and_hasher(
+                        shard_capacity,
+                        hasher.clone(),
+                        options.ordered_index,
+                    ))
+                })
+                .collect(),
+            hasher,
+            events: broadcast::channel(options.event_capacity.max(1)).0,
+            rng: AtomicU64::new(
+                options
+                    .jitter_seed
+                    .unwrap_or_else(|| RandomState::new().hash_one(0u64)),
+            ),
+            load_permits: options
+                .max_concurrent_loads
+                .map(|limit| Semaphore::new(limit.max(1))),
+            closed: AtomicBool::new(false),
+            breaker: options.circuit_breaker.map(Breaker::new),
+            load_latency: LatencyHistogram::default(),
+            clock: options.clock_granularity.map(CoarseClock::start),
+            options,
+            hooks,
+            tick: AtomicU64::new(0),
+            cleared_at: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            eviction: Mutex::new(()),
+            #[cfg(any(feature = "tokio", feature = "async-std"))]
+            refreshing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Waits for a free loader slot when `max_concurrent_loads` is set
+    ///
+    /// The slot is released when the returned permit is dropped.
+    pub(crate) async fn load_permit(&self) -> Option<SemaphorePermit<'_>> {
+        self.load_permits.as_ref()?.acquire().await.ok()
+    }
+
+    /// Stops new loads from starting
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    /// The time expiry is checked against on reads
+    ///
+    /// With `clock_granularity` set this is the cached clock, which lags the
+    /// real time by up to one granularity. It's set back by `expiry_tolerance`,
+    /// so entries stay live for that long past their expiry.
+    pub(crate) fn now(&self) -> SystemTime {
+        let now = self.wall_now();
+        now.checked_sub(self.options.expiry_tolerance)
+            .unwrap_or(now)
+    }
+
+    /// Whether `item` has expired, by the same clock and tolerance as reads
+    ///
+    /// Every expiry check in the store goes through this or [`now`](Self::now),
+    /// so nothing purges, evicts as expired or refuses to update an entry that
+    /// a read would still serve.
+    pub(crate) fn is_expired(&self, item: &Expiring<V>) -> bool {
+        item.is_expired_at(self.now())
+    }
+
+    /// The current time, from the cached clock if `clock_granularity` is set
+    fn wall_now(&self) -> SystemTime {
+        self.clock
+            .as_ref()
+            .map_or_else(SystemTime::now, |clock| clock.now())
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<CacheEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| self.read(shard).map_or(0, |map| map.len()))
+            .sum()
+    }
+
+    pub(crate) fn total_weight(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| self.read(shard).map_or(0, |map| map.weight()))
+            .sum()
+    }
+
+    pub(crate) fn active_size(&self) -> usize {
+        let now = self.now();
+        self.shards
+            .iter()
+            .map(|shard| {
+                self.read(shard).map_or(0, |map| {
+                    map.iter()
+                        .filter(|(_, entry)| !entry.item.is_expired_at(now))
+                        .count()
+                })
+            })
+            .sum()
+    }
+
+    pub(crate) fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard_index(&self, identifier: &str) -> usize {
+        let hash = self.hasher.hash_one(identifier);
+        (hash % self.shards.len() as u64) as usize
+    }
+
+    fn shard(&self, identifier: &str) -> &RwLock<Shard<V, S>> {
+        &self.shards[self.shard_index(identifier)]
+    }
+
+    /// Takes a shard's read lock, handling poisoning as the [`PoisonPolicy`] says
+    fn read<'a>(&self, shard: &'a RwLock<Shard<V, S>>) -> Option<ShardReadGuard<'a, V, S>> {
+        shard
+            .read()
+            .or_else(|poisoned| self.on_poisoned(shard, poisoned))
+            .ok()
+    }
+
+    /// Like [`read`](Self::read), returning `None` instead of waiting for a writer
+    fn try_read<'a>(&self, shard: &'a RwLock<Shard<V, S>>) -> Option<ShardReadGuard<'a, V, S>> {
+        match shard.try_read() {
+            Ok(map) => Some(map),
+            Err(TryLockError::Poisoned(poisoned)) => self.on_poisoned(shard, poisoned).ok(),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Takes a shard's write lock, handling poisoning as the [`PoisonPolicy`] says
+    fn write<'a>(&self, shard: &'a RwLock<Shard<V, S>>) -> Option<ShardWriteGuard<'a, V, S>> {
+        shard
+            .write()
+            .or_else(|poisoned| self.on_poisoned(shard, poisoned))
+            .ok()
+    }
+
+    /// Recovers the guard of a poisoned shard, or gives up on it
+    fn on_poisoned<G>(
+        &self,
+        shard: &RwLock<Shard<V, S>>,
+        poisoned: PoisonError<G>,
+    ) -> Result<G, PoisonError<G>> {
+        match self.options.poison_policy {
+            // Callers that can report it check for poisoning themselves
+            PoisonPolicy::Ignore | PoisonPolicy::Error => Err(poisoned),
+            PoisonPolicy::Recover => {
+                shard.clear_poison();
+                Ok(poisoned.into_inner())
+            }
+        }
+    }
+
+    /// Maps every non-expired entry through `f`, one shard read lock at a time
+    pub(crate) fn collect_live<T>(&self, f: impl Fn(&str, &Expiring<V>) -> T) -> Vec<T> {
+        self.collect_live_entries(|identifier, entry| f(identifier, &entry.item))
+    }
+
+    /// Like [`collect_live`](Self::collect_live), handing `f` the whole entry
+    pub(crate) fn collect_live_entries<T>(&self, f: impl Fn(&str, &Entry<V>) -> T) -> Vec<T> {
+        let now = self.now();
+        let mut collected = Vec::new();
+        for shard in &self.shards {
+            if let Some(map) = self.read(shard) {
+                collected.extend(
+                    map.iter()
+                        .filter(|(_, entry)| !entry.item.is_expired_at(now))
+                        .map(|(identifier, entry)| f(identifier, entry)),
+                );
+            }
+        }
+        collected
+    }
+
+    pub(crate) fn next_tick(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Looks up a live entry, counting it as a hit for LRU and sliding expiry
+    ///
+    /// The flag is set when the entry has entered its refresh-ahead window.
+    pub(crate) fn get_non_expired(&self, identifier: &str) -> Option<(Expiring<V>, bool)> {
+        self.get_non_expired_with(identifier, Expiring::clone)
+    }
+
+    /// Like [`get_non_expired`](Self::get_non_expired), returning `f` of the entry instead of a clone
+    ///
+    /// `f` runs under the shard's lock.
+    pub(crate) fn get_non_expired_with<U>(
+        &self,
+        identifier: &str,
+        f: impl FnOnce(&Expiring<V>) -> U,
+    ) -> Option<(U, bool)> {
+        let found = match self.options.sliding {
+            Some(_) => self.get_non_expired_sliding(identifier, f),
+            None => self.get_non_expired_shared(identifier, f),
+        };
+        // Aging locks every shard, so it has to wait until the entry's lock is released
+        if found.is_some() && self.options.eviction_policy == EvictionPolicy::Lfu {
+            self.age_frequencies();
+        }
+        found
+    }
+
+    fn get_non_expired_shared<U>(
+        &self,
+        identifier: &str,
+        f: impl FnOnce(&Expiring<V>) -> U,
+    ) -> Option<(U, bool)> {
+        let map = self.read(self.shard(identifier))?;
+        let entry = map.get(identifier)?;
+        if self.is_expired(&entry.item) {
+            return None;
+        }
+
+        self.record_hit(entry);
+        Some((f(&entry.item), self.is_due_for_refresh(entry)))
+    }
+
+    /// Looks up a live entry without waiting for the shard lock or counting a hit
+    pub(crate) fn try_peek(&self, identifier: &str) -> Option<Expiring<V>> {
+        let map = self.try_read(self.shard(identifier))?;
+        map.get(identifier)
+            .filter(|entry| !self.is_expired(&entry.item))
+            .map(|entry| entry.item.clone())
+    }
+
+    /// Whether a panic poisoned the lock of the shard holding `identifier`
+    pub(crate) fn is_poisoned(&self, identifier: &str) -> bool {
+        self.shard(identifier).is_poisoned()
+    }
+
+    /// When a live entry was last hit, without counting this as a hit
+    pub(crate) fn last_accessed(&self, identifier: &str) -> Option<SystemTime> {
+        let map = self.read(self.shard(identifier))?;
+        map.get(identifier)
+            .filter(|entry| !self.is_expired(&entry.item))
+            .map(Entry::last_accessed)
+    }
+
+    /// Time left on a live entry, without counting it as a hit
+    pub(crate) fn time_to_live(&self, identifier: &str) -> Option<Duration> {
+        let map = self.read(self.shard(identifier))?;
+        map.get(identifier)
+            .filter(|entry| !self.is_expired(&entry.item))
+            .and_then(|entry| entry.item.time_to_live())
+    }
+
+    fn get_non_expired_sliding<U>(
+        &self,
+        identifier: &str,
+        f: impl FnOnce(&Expiring<V>) -> U,
+    ) -> Option<(U, bool)> {
+        let mut map = self.write(self.shard(identifier))?;
+        let entry = map.get_mut(identifier)?;
+        if self.is_expired(&entry.item) {
+            return None;
+        }
+
+        self.slide(entry);
+        self.record_hit(entry);
+        Some((f(&entry.item), self.is_due_for_refresh(entry)))
+    }
+
+    /// Pushes a hit entry's expiry out to at least `sliding` from now, if set
+    fn slide(&self, entry: &mut Entry<V>) {
+        let Some(sliding) = self.options.sliding else {
+            return;
+        };
+        // Too far out to represent means never expiring
+        let extended = SystemTime::now().checked_add(sliding);
+        if let Some(expires_at) = entry.item.expires_at
+            && extended.is_none_or(|extended| expires_at < extended)
+        {
+            entry.set_expiry(extended, self.options.max_age);
+        }
+    }
+
+    fn record_hit(&self, entry: &Entry<V>) {
+        entry.last_access.store(self.next_tick(), Ordering::Relaxed);
+        entry
+            .accessed_at
+            .store(nanos_since_epoch(self.wall_now()), Ordering::Relaxed);
+        entry.frequency.fetch_add(1, Ordering::Relaxed);
+        entry.hit_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Halves every entry's frequency once per aging window of hits
+    ///
+    /// Without aging, a key that was hot long ago would outrank everything
+    /// that's popular now and never be evicted.
+    fn age_frequencies(&self) {
+        let window = self
+            .options
+            .max_capacity
+            .unwrap_or(DEFAULT_LFU_CAPACITY)
+            .max(1) as u64
+            * LFU_AGING_FACTOR;
+        if !(self.hits.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(window) {
+            return;
+        }
+        for shard in &self.shards {
+            if let Some(map) = self.read(shard) {
+                for (_, entry) in map.iter() {
+                    let frequency = entry.frequency.load(Ordering::Relaxed);
+                    entry.frequency.store(frequency / 2, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    fn is_due_for_refresh(&self, entry: &Entry<V>) -> bool {
+        entry.item.is_stale_at(self.now())
+            || self
+                .options
+                .refresh_ahead
+                .is_some_and(|threshold| entry.is_due_for_refresh(threshold))
+    }
+
+    pub(crate) fn insert_item(
+        &self,
+        identifier: String,
+        key: Option<StoredKey>,
+        item: Expiring<V>,
+    ) {
+        self.insert_items(vec![(identifier, key, item)]);
+    }
+
+    /// Stores a freshly loaded item unless a newer value was stored meanwhile
+    ///
+    /// `started` is the tick taken before the loader was called. If the live
+    /// entry for the identifier was produced after that, by a later load or a
+    /// direct insert, it's kept and this returns `false`.
+    pub(crate) fn insert_loaded(
+        &self,
+        identifier: String,
+        key: Option<StoredKey>,
+        item: Expiring<V>,
+        started: u64,
+    ) -> bool {
+        self.store_items(vec![(identifier, key, item)], Origin::Load { started }) == 1
+    }
+
+    /// Stores a background refresh's item like [`insert_loaded`](Self::insert_loaded)
+    ///
+    /// The item is dropped if the entry it refreshes was removed meanwhile.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) fn insert_refreshed(
+        &self,
+        identifier: String,
+        key: Option<StoredKey>,
+        item: Expiring<V>,
+        started: u64,
+    ) {
+        self.store_items(vec![(identifier, key, item)], Origin::Refresh { started });
+    }
+
+    /// Stores a batch of items, taking each shard's write lock only once
+    ///
+    /// Items later in the batch win over earlier ones with the same identifier.
+    pub(crate) fn insert_items(&self, items: Vec<NewItem<V>>) {
+        self.store_items(items, Origin::Direct);
+    }
+
+    /// Stores a freshly loaded item like [`insert_loaded`](Self::insert_loaded),
+    /// also returning the item it replaced even if that had expired
+    ///
+    /// The previous item is taken under the same write lock that installs the
+    /// new one. If the item isn't stored, because it's already expired or a
+    /// newer write or a clear landed during the load, this returns `false`
+    /// with the current item instead.
+    pub(crate) fn replace_loaded(
+        &self,
+        identifier: String,
+        key: Option<StoredKey>,
+        item: Expiring<V>,
+        started: u64,
+    ) -> (bool, Option<Expiring<V>>) {
+        if item.remaining_ttl().is_zero() {
+            return (false, self.stored_item(&identifier));
+        }
+        let (inserted, replaced) = self.write_items(
+            vec![(identifier.clone(), key, item)],
+            Origin::Load { started },
+        );
+        if inserted.is_empty() {
+            self.finish_store(inserted, replaced);
+            return (false, self.stored_item(&identifier));
+        }
+        let previous = replaced
+            .iter()
+            .find(|(replaced, _, _)| *replaced == identifier)
+            .map(|(_, item, _)| item.clone());
+        self.finish_store(inserted, replaced);
+        (true, previous)
+    }
+
+    fn stored_item(&self, identifier: &str) -> Option<Expiring<V>> {
+        let map = self.read(self.shard(identifier))?;
+        map.get(identifier).map(|entry| entry.item.clone())
+    }
+
+    /// Stores items, returning how many were written
+    fn store_items(&self, items: Vec<NewItem<V>>, origin: Origin) -> usize {
+        let (inserted, replaced) = self.write_items(items, origin);
+        self.finish_store(inserted, replaced)
+    }
+
+    /// Writes items into their shards, returning the stored identifiers and the entries they replaced
+    fn write_items(
+        &self,
+        items: Vec<NewItem<V>>,
+        origin: Origin,
+    ) -> (Vec<String>, Vec<Evicted<V>>) {
+        let started = match origin {
+            Origin::Direct => None,
+            Origin::Load { started } => Some(started),
+            #[cfg(any(feature = "tokio", feature = "async-std"))]
+            Origin::Refresh { started } => Some(started),
+        };
+        let mut by_shard: Vec<Vec<_>> = self.shards.iter().map(|_| Vec::new()).collect();
+        for (identifier, key, item) in items {
+            let weight = self
+                .hooks
+                .weigher
+                .as_ref()
+                .map_or(0, |weigher| weigher(&item.value));
+            by_shard[self.shard_index(&identifier)].push((identifier, key, item, weight));
+        }
+
+        let mut evicted = Vec::new();
+        let mut inserted = Vec::new();
+        for (shard, items) in self.shards.iter().zip(by_shard) {
+            if items.is_empty() {
+                continue;
+            }
+            let Some(mut map) = self.write(shard) else {
+                continue;
+            };
+            for (identifier, key, item, weight) in items {
+                // A clear that ran during the load wins over the loaded value
+                if started.is_some_and(|started| started < self.cleared_at.load(Ordering::Relaxed))
+                {
+                    continue;
+                }
+                #[cfg(any(feature = "tokio", feature = "async-std"))]
+                if matches!(origin, Origin::Refresh { .. }) && map.get(&identifier).is_none() {
+                    continue;
+                }
+                if let Some(started) = started
+                    && let Some(existing) = map.get(&identifier)
+                    && !self.is_expired(&existing.item)
+                    && existing.version > started
+                {
+                    continue;
+                }
+
+                if self.put_entry(
+                    &mut map,
+                    (identifier.clone(), key, item),
+                    weight,
+                    started,
+                    &mut evicted,
+                ) {
+                    inserted.push(identifier);
+                }
+            }
+        }
+        (inserted, evicted)
+    }
+
+    /// Puts an item into a locked shard, returning whether it was stored
+    ///
+    /// The entry it replaces is added to `evicted`. `version` is the tick the
+    /// load started at, if the item was loaded. The replaced entry's hit count
+    /// carries over, and so does its remembered key when no key is given.
+    fn put_entry(
+        &self,
+        map: &mut Shard<V, S>,
+        (identifier, key, item): NewItem<V>,
+        weight: usize,
+        version: Option<u64>,
+        evicted: &mut Vec<Evicted<V>>,
+    ) -> bool {
+        // An item that can never fit is dropped instead of flushing everything else
+        if self
+            .options
+            .max_weight
+            .is_some_and(|max_weight| weight > max_weight)
+        {
+            if let Some(old) = map.remove(&identifier) {
+                evicted.push((identifier, old.item, EvictionReason::Replaced));
+            }
+            return false;
+        }
+
+        let mut entry = Entry::new(item, self.next_tick(), weight, self.options.max_age);
+        if let Some(version) = version {
+            entry.version = version;
+        }
+        if let Some(old) = map.get(&identifier) {
+            entry.key = key.or_else(|| old.key.clone());
+            entry.hit_count = AtomicU64::new(old.hit_count.load(Ordering::Relaxed));
+        } else {
+            entry.key = key;
+        }
+        if let Some(old) = map.insert(identifier.clone(), entry) {
+            let reason = if self.is_expired(&old.item) {
+                EvictionReason::Expired
+            } else {
+                EvictionReason::Replaced
+            };
+            evicted.push((identifier, old.item, reason));
+        }
+        true
+    }
+
+    /// Returns the live value for `identifier`, or stores the item `make` builds
+    ///
+    /// The check and the insert happen under one write lock, so concurrent
+    /// callers never both store a value; `make` and the weigher run under that
+    /// lock. A hit counts as a use of the entry. If the shard can't be locked,
+    /// the built value is returned without being stored.
+    pub(crate) fn get_or_insert_item(
+        &self,
+        identifier: &str,
+        key: Option<StoredKey>,
+        make: impl FnOnce() -> Expiring<V>,
+    ) -> V {
+        let Some(mut map) = self.write(self.shard(identifier)) else {
+            return make().value;
+        };
+        if let Some(entry) = map.get_mut(identifier)
+            && !self.is_expired(&entry.item)
+        {
+            self.slide(entry);
+            self.record_hit(entry);
+            let value = entry.item.value.clone();
+            drop(map);
+            if self.options.eviction_policy == EvictionPolicy::Lfu {
+                self.age_frequencies();
+            }
+            return value;
+        }
+
+        let item = make();
+        let value = item.value.clone();
+        let weight = self
+            .hooks
+            .weigher
+            .as_ref()
+            .map_or(0, |weigher| weigher(&item.value));
+        let mut replaced = Vec::new();
+        let stored = self.put_entry(
+            &mut map,
+            (identifier.to_string(), key, item),
+            weight,
+            None,
+            &mut replaced,
+        );
+        drop(map);
+        let inserted = if stored {
+            vec![identifier.to_string()]
+        } else {
+            Vec::new()
+        };
+        self.finish_store(inserted, replaced);
+        value
+    }
+
+    /// Reports replaced entries, evicts down to capacity and publishes the inserts
+    fn finish_store(&self, inserted: Vec<String>, replaced: Vec<Evicted<V>>) -> usize {
+        self.notify_evicted(replaced);
+        let evicted = self.evict_to_capacity(&inserted.iter().map(String::as_str).collect());
+        let stored = inserted.len();
+        for identifier in inserted {
+            let _ = self.events.send(CacheEvent::Inserted(identifier));
+        }
+        self.notify_evicted(evicted);
+        stored
+    }
+
+    /// Changes a live entry's value in place, keeping its expiry
+    ///
+    /// The change counts as a newer write, so a load that started before it
+    /// won't overwrite it. If the entry grew past the weight limit, other
+    /// entries are evicted to make room.
+    pub(crate) fn update(&self, identifier: &str, f: impl FnOnce(&mut V)) -> bool {
+        let updated = match self.write(self.shard(identifier)) {
+            Some(mut map) => map.update(
+                identifier,
+                self.next_tick(),
+                self.now(),
+                f,
+                self.hooks.weigher.as_deref(),
+            ),
+            None => false,
+        };
+        if updated && self.hooks.weigher.is_some() {
+            let evicted = self.evict_to_capacity(&HashSet::from([identifier]));
+            self.notify_evicted(evicted);
+        }
+        updated
+    }
+
+    /// Moves a live entry's expiry to `when`, `None` meaning never, returning whether the entry existed
+    pub(crate) fn set_expiry(&self, identifier: &str, when: Option<SystemTime>) -> bool {
+        let Some(mut map) = self.write(self.shard(identifier)) else {
+            return false;
+        };
+        match map.get(identifier) {
+            Some(entry) if !self.is_expired(&entry.item) => {
+                map.set_expiry(identifier, when, self.options.max_age);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets the stored item for `identifier` even if it has expired, with its age
+    ///
+    /// The age is how long ago the item was stored. Doesn't count as a hit.
+    pub(crate) fn peek_stored(&self, identifier: &str) -> Option<(Expiring<V>, Duration)> {
+        let map = self.read(self.shard(identifier))?;
+        let entry = map.get(identifier)?;
+        let age = SystemTime::now()
+            .duration_since(entry.created_at)
+            .unwrap_or(Duration::ZERO);
+        Some((entry.item.clone(), age))
+    }
+
+    /// Gets the stored item for `identifier` even if it has expired
+    ///
+    /// An expired item's expiry is pushed out to `grace` from now, still
+    /// capped by `max_age`, so it's served as a hit until then.
+    pub(crate) fn revive_stale(&self, identifier: &str, grace: Duration) -> Option<Expiring<V>> {
+        let mut map = self.write(self.shard(identifier))?;
+        let entry = map.get_mut(identifier)?;
+        if self.is_expired(&entry.item) && !grace.is_zero() {
+            let revived = SystemTime::now().checked_add(grace);
+            entry.set_expiry(revived, self.options.max_age);
+        }
+        Some(entry.item.clone())
+    }
+
+    /// Whether the stored entry expired no more than `within` ago
+    pub(crate) fn expired_within(&self, identifier: &str, within: Duration) -> bool {
+        let Some(map) = self.read(self.shard(identifier)) else {
+            return false;
+        };
+        map.get(identifier)
+            .and_then(|entry| entry.item.expires_at)
+            .and_then(|expires_at| self.now().duration_since(expires_at).ok())
+            .is_some_and(|expired_for| expired_for <= within)
+    }
+
+    pub(crate) fn remove(&self, identifier: &str) -> Option<Entry<V>> {
+        self.write(self.shard(identifier))
+            .and_then(|mut map| map.remove(identifier))
+    }
+
+    /// Marks every load started so far as outdated, so its result isn't stored
+    ///
+    /// Must be called before the shards are emptied: a load that stores its
+    /// result before this is removed by the clear, and one that stores it
+    /// after sees the mark under the shard lock.
+    fn mark_cleared(&self) {
+        self.cleared_at
+            .fetch_max(self.next_tick(), Ordering::Relaxed);
+    }
+
+    /// Removes every entry, reporting each with `reason`
+    ///
+    /// Loads that are still running when this is called won't store their results.
+    pub(crate) fn drain(&self, reason: EvictionReason) -> Vec<Evicted<V>> {
+        self.mark_cleared();
+        let mut evicted = Vec::new();
+        for shard in &self.shards {
+            if let Some(mut map) = self.write(shard) {
+                evicted.extend(
+                    map.drain()
+                        .into_iter()
+                        .map(|(identifier, entry)| (identifier, entry.item, reason)),
+                );
+            }
+        }
+        evicted
+    }
+
+    /// Removes every expired entry, taking them from each shard's expiry queue
+    pub(crate) fn purge_expired(&self) -> Vec<Evicted<V>> {
+        let now = self.now();
+        let mut evicted = Vec::new();
+        for shard in &self.shards {
+            if let Some(mut map) = self.write(shard) {
+                evicted.extend(
+                    map.extract_expired(now)
+                        .into_iter()
+                        .map(|(identifier, entry)| {
+                            (identifier, entry.item, EvictionReason::Expired)
+                        }),
+                );
+            }
+        }
+        evicted
+    }
+
+    /// Removes every entry whose identifier starts with `prefix`
+    ///
+    /// Like [`drain`](Self::drain), loads still running won't store their results.
+    pub(crate) fn remove_prefixed(&self, prefix: &str) -> Vec<Evicted<V>> {
+        self.mark_cleared();
+        let mut evicted = Vec::new();
+        for shard in &self.shards {
+            if let Some(mut map) = self.write(shard) {
+                let identifiers: Vec<String> = map
+                    .identifiers_in((Bound::Unbounded, Bound::Unbounded), Some(prefix))
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                evicted.extend(identifiers.into_iter().filter_map(|identifier| {
+                    let entry = map.remove(&identifier)?;
+                    Some((identifier, entry.item, EvictionReason::Manual))
+                }));
+            }
+        }
+        evicted
+    }
+
+    /// Collects `f` of every live entry whose identifier is within `range`, sorted by identifier
+    pub(crate) fn collect_range<T>(
+        &self,
+        range: (Bound<&str>, Bound<&str>),
+        f: impl Fn(&str, &Expiring<V>) -> T,
+    ) -> Vec<T> {
+        let now = self.now();
+        let mut found = Vec::new();
+        for shard in &self.shards {
+            if let Some(map) = self.read(shard) {
+                for identifier in map.identifiers_in(range, None) {
+                    if let Some(entry) = map.get(identifier)
+                        && !entry.item.is_expired_at(now)
+                    {
+                        found.push((identifier.clone(), f(identifier, &entry.item)));
+                    }
+                }
+            }
+        }
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        found.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Removes every entry `keep` rejects, letting it modify the ones it keeps
+    ///
+    /// Returns the rejected entries, followed by any evicted because kept
+    /// values grew past the weight limit.
+    pub(crate) fn retain(
+        &self,
+        mut keep: impl FnMut(&str, &mut Expiring<V>) -> bool,
+    ) -> Vec<Evicted<V>> {
+        let mut evicted = Vec::new();
+        for shard in &self.shards {
+            if let Some(mut map) = self.write(shard) {
+                evicted.extend(
+                    map.retain(
+                        &mut keep,
+                        self.options.max_age,
+                        self.hooks.weigher.as_deref(),
+                    )
+                    .into_iter()
+                    .map(|(identifier, entry)| (identifier, entry.item, EvictionReason::Manual)),
+                );
+            }
+        }
+        if self.hooks.weigher.is_some() {
+            evicted.extend(self.evict_to_capacity(&HashSet::new()));
+        }
+        evicted
+    }
+
+    /// Publishes an event and runs the eviction callback for each removed entry
+    ///
+    /// Must be called after all locks are released so the callback can safely
+    /// call back into the cache.
+    pub(crate) fn notify_evicted(&self, evicted: Vec<Evicted<V>>) {
+        for (identifier, item, reason) in evicted {
+            if let Some(on_evict) = &self.hooks.on_evict {
+                on_evict(&identifier, &item.value, reason);
+            }
+            let event = match reason {
+                EvictionReason::Expired => CacheEvent::Expired(identifier),
+                _ => CacheEvent::Evicted(identifier),
+            };
+            let _ = self.events.send(event);
+        }
+    }
+
+    fn over_capacity(&self) -> bool {
+        self.options
+            .max_capacity
+            .is_some_and(|capacity| self.size() > capacity)
+            || self.over_weight()
+    }
+
+    fn over_weight(&self) -> bool {
+        self.options
+            .max_weight
+            .is_some_and(|max_weight| self.total_weight() > max_weight)
+    }
+
+    /// Evicts entries until the cache fits its capacity and weight limits
+    ///
+    /// Expired entries go first, then the least recently used, or with LFU the
+    /// least frequently used with ties going to the least recent. The entries in
+    /// `inserted` are only evicted once nothing else is left, so a new entry
+    /// isn't pushed straight back out by its own insert. Finding a victim
+    /// scans every shard, so eviction is O(n) in the number of cached entries.
+    /// Once over `max_capacity`, the eviction batch is freed in one go.
+    fn evict_to_capacity(&self, inserted: &HashSet<&str>) -> Vec<Evicted<V>> {
+        if !self.over_capacity() {
+            return Vec::new();
+        }
+        // Make room for a whole batch, but never evict down to nothing
+        let batch = self.options.eviction_batch.max(1);
+        let max_size = self
+            .options
+            .max_capacity
+            .map(|capacity| capacity.saturating_sub(batch - 1).max(capacity.min(1)));
+        self.evict_while(
+            || max_size.is_some_and(|max_size| self.size() > max_size) || self.over_weight(),
+            inserted,
+        )
+    }
+
+    /// Evicts entries until at most `target_size` are left, then releases spare capacity
+    ///
+    /// Victims are picked in the same order as for capacity eviction.
+    pub(crate) fn shrink_to(&self, target_size: usize) -> Vec<Evicted<V>> {
+        let evicted = self.evict_while(|| self.size() > target_size, &HashSet::new());
+        for shard in &self.shards {
+            if let Some(mut map) = self.write(shard) {
+                map.shrink_to_fit();
+            }
+        }
+        evicted
+    }
+
+    /// Evicts entries in eviction policy order while `over` holds
+    ///
+    /// All shards are scanned once to rank every entry, and victims are then
+    /// popped off a heap until `over` stops holding, so evicting a batch of k
+    /// entries costs one scan plus k heap pops. Another scan only happens if
+    /// concurrent inserts keep the cache over after every ranked entry is gone.
+    fn evict_while(&self, over: impl Fn() -> bool, inserted: &HashSet<&str>) -> Vec<Evicted<V>> {
+        let mut evicted = Vec::new();
+        let Ok(_guard) = self.eviction.lock() else {
+            return evicted;
+        };
+
+        while over() {
+            let mut candidates = self.rank_candidates(inserted);
+            if candidates.is_empty() {
+                break;
+            }
+            while let Some(Reverse((_, identifier))) = candidates.pop() {
+                if let Some(entry) = self.remove(&identifier) {
+                    let reason = if self.is_expired(&entry.item) {
+                        EvictionReason::Expired
+                    } else {
+                        EvictionReason::Capacity
+                    };
+                    evicted.push((identifier, entry.item, reason));
+                }
+                if !over() {
+                    break;
+                }
+            }
+        }
+        evicted
+    }
+
+    /// Ranks every entry for eviction in one pass, the first to go on top
+    ///
+    /// Expired entries rank first, then entries not among `inserted`, then by
+    /// recency or frequency depending on the eviction policy.
+    fn rank_candidates(&self, inserted: &HashSet<&str>) -> BinaryHeap<Reverse<EvictionRank>> {
+        let now = self.now();
+        let mut candidates = Vec::new();
+        for shard in &self.shards {
+            let Some(map) = self.read(shard) else {
+                continue;
+            };
+            candidates.extend(map.iter().map(|(identifier, entry)| {
+                let last_access = entry.last_access.load(Ordering::Relaxed);
+                let usage = match self.options.eviction_policy {
+                    EvictionPolicy::Lru => (last_access, 0),
+                    EvictionPolicy::Lfu => (entry.frequency.load(Ordering::Relaxed), last_access),
+                };
+                let rank = (
+                    !entry.item.is_expired_at(now),
+                    inserted.contains(identifier.as_str()),
+                    usage,
+                );
+                Reverse((rank, identifier.clone()))
+            }));
+        }
+        BinaryHeap::from(candidates)
+    }
+
+    /// Runs a loaded item's value through the transform hook, if there is one
+    pub(crate) fn transform(&self, item: Expiring<V>) -> Expiring<V> {
+        match &self.hooks.transform {
+            Some(transform) => item.map(|value| transform(value)),
+            None => item,
+        }
+    }
+
+    /// Rescales a loaded item's TTL by how often the entry it replaces was hit
+    ///
+    /// Items that never expire or already have are left alone.
+    pub(crate) fn adapt_ttl(&self, identifier: &str, mut item: Expiring<V>) -> Expiring<V> {
+        let Some(adaptive_ttl) = &self.hooks.adaptive_ttl else {
+            return item;
+        };
+        let now = SystemTime::now();
+        let Some(base_ttl) = item
+            .expires_at
+            .and_then(|expires_at| expires_at.duration_since(now).ok())
+        else {
+            return item;
+        };
+        let accesses = self
+            .read(self.shard(identifier))
+            .and_then(|map| {
+                map.get(identifier)
+                    .map(|entry| entry.hit_count.load(Ordering::Relaxed))
+            })
+            .unwrap_or(0);
+        if let Some(expires_at) = now.checked_add(adaptive_ttl(accesses, base_ttl)) {
+            item.expires_at = Some(expires_at);
+        }
+        item
+    }
+
+    /// Pushes a loaded item's expiry back by a random offset in `[0, jitter)`
+    pub(crate) fn apply_jitter(&self, mut item: Expiring<V>) -> Expiring<V> {
+        let Some(jitter) = self.options.jitter else {
+            return item;
+        };
+        let nanos = u64::try_from(jitter.as_nanos()).unwrap_or(u64::MAX);
+        if nanos == 0 {
+            return item;
+        }
+        if let Some(expires_at) = item.expires_at {
+            let offset = Duration::from_nanos(self.next_random() % nanos);
+            item.expires_at = expires_at.checked_add(offset).or(Some(expires_at));
+        }
+        item
+    }
+
+    /// Marks `identifier` as being refreshed, or returns `None` if it already is
+    ///
+    /// The mark is cleared when the returned guard is dropped.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub(crate) fn start_refresh(self: &Arc<Self>, identifier: &str) -> Option<RefreshGuard<V, S>> {
+        let mut refreshing = self.refreshing.lock().ok()?;
+        if !refreshing.insert(identifier.to_string()) {
+            return None;
+        }
+        Some(RefreshGuard {
+            store: Arc::clone(self),
+            identifier: identifier.to_string(),
+        })
+    }
+
+    /// Steps the cache's splitmix64 generator
+    fn next_random(&self) -> u64 {
+        let mut z = self
+            .rng
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// An in-flight background refresh of one entry
+///
+/// Dropping the guard clears the entry's refresh mark, even if the loader panics,
+/// so a later hit can start a new refresh.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub(crate) struct RefreshGuard<V, S> {
+    pub(crate) store: Arc<Store<V, S>>,
+    identifier: String,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl<V, S> Drop for RefreshGuard<V, S> {
+    fn drop(&mut self) {
+        if let Ok(mut refreshing) = self.store.refreshing.lock() {
+            refreshing.remove(&self.identifier);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! [`tower`](https://docs.rs/tower) middleware memoizing a service's responses
+
+use crate::cache::{BoxError, Cache, Expiring, LoadFuture};
+use crate::error::CacheError;
+use std::future::{Future, poll_fn};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Loader that calls a clone of the wrapped service
+pub type ServiceLoader<Req, Resp> = Box<dyn Fn(Req) -> LoadFuture<Resp> + Send + Sync>;
+
+/// Layer caching an inner service's responses by request, see [`CacheService`]
+pub struct CacheLayer<Req, G> {
+    ttl: Duration,
+    key_mapper: G,
+    _request: PhantomData<fn(Req)>,
+}
+
+impl<Req, G> CacheLayer<Req, G>
+where
+    G: Fn(&Req) -> String,
+{
+    /// Caches each response for `ttl` under the key `key_mapper` gives its request
+    pub fn new(ttl: Duration, key_mapper: G) -> Self {
+        Self {
+            ttl,
+            key_mapper,
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<Req, G: Clone> Clone for CacheLayer<Req, G> {
+    fn clone(&self) -> Self {
+        Self {
+            ttl: self.ttl,
+            key_mapper: self.key_mapper.clone(),
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<Req, G, S> Layer<S> for CacheLayer<Req, G>
+where
+    Req: Clone + Send + Sync + 'static,
+    G: Fn(&Req) -> String + Clone + Send + Sync,
+    S: Service<Req> + Clone + Send + Sync + 'static,
+    S::Response: Clone + Send + Sync + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    type Service = CacheService<Req, S::Response, G>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let ttl = self.ttl;
+        let load: ServiceLoader<Req, S::Response> = Box::new(move |request| {
+            let mut inner = inner.clone();
+            Box::pin(async move {
+                poll_fn(|cx| inner.poll_ready(cx))
+                    .await
+                    .map_err(Into::into)?;
+                let response = inner.call(request).await.map_err(Into::into)?;
+                Ok(Expiring::with_duration(response, ttl))
+            })
+        });
+        CacheService {
+            cache: Arc::new(Cache::new(load, self.key_mapper.clone())),
+        }
+    }
+}
+
+/// Service answering requests from a cache, calling the inner service on a miss
+///
+/// Hits never touch the inner service, and concurrent misses for the same key
+/// share one inner call. Failed calls aren't cached. Each miss calls a clone
+/// of the inner service, so this is always ready.
+pub struct CacheService<Req, Resp, G>
+where
+    Req: Clone,
+    Resp: Clone,
+    G: Fn(&Req) -> String,
+{
+    cache: Arc<Cache<Req, Resp, ServiceLoader<Req, Resp>, G>>,
+}
+
+impl<Req, Resp, G> CacheService<Req, Resp, G>
+where
+    Req: Clone,
+    Resp: Clone,
+    G: Fn(&Req) -> String,
+{
+    /// The underlying cache, for stats or invalidation
+    pub fn cache(&self) -> &Arc<Cache<Req, Resp, ServiceLoader<Req, Resp>, G>> {
+        &self.cache
+    }
+}
+
+impl<Req, Resp, G> Clone for CacheService<Req, Resp, G>
+where
+    Req: Clone,
+    Resp: Clone,
+    G: Fn(&Req) -> String,
+{
+    fn clone(&self) -> Self {
+        Self {
+            cache: Arc::clone(&self.cache),
+        }
+    }
+}
+
+impl<Req, Resp, G> Service<Req> for CacheService<Req, Resp, G>
+where
+    Req: Clone + Send + Sync + 'static,
+    Resp: Clone + Send + Sync + 'static,
+    G: Fn(&Req) -> String + Send + Sync + 'static,
+{
+    type Response = Resp;
+    type Error = CacheError;
+    type Future = Pin<Box<dyn Future<Output = Result<Resp, CacheError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        let cache = Arc::clone(&self.cache);
+        Box::pin(async move { cache.get(request).await })
+    }
+}
@@ -0,0 +1,39 @@
+use crate::cache::BoxError;
+use crate::error::CacheError;
+use std::any::Any;
+use std::future::{Future, poll_fn};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::pin;
+use std::task::Poll;
+
+/// Calls a loader and awaits its future, turning a panic in either into an error
+///
+/// A panic becomes [`CacheError::LoaderPanicked`], so it's handled like any
+/// other load failure instead of unwinding through the cache. No cache lock is
+/// held while the loader runs, so nothing is left poisoned.
+pub(crate) async fn catch_loader_panic<T, Fut>(load: impl FnOnce() -> Fut) -> Result<T, CacheError>
+where
+    Fut: Future<Output = Result<T, BoxError>>,
+{
+    let future = match catch_unwind(AssertUnwindSafe(load)) {
+        Ok(future) => future,
+        Err(payload) => return Err(panicked(payload)),
+    };
+    let mut future = pin!(future);
+    poll_fn(
+        |cx| match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(poll) => poll.map_err(CacheError::Load),
+            Err(payload) => Poll::Ready(Err(panicked(payload))),
+        },
+    )
+    .await
+}
+
+fn panicked(payload: Box<dyn Any + Send>) -> CacheError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+    CacheError::LoaderPanicked(message)
+}
@@ -0,0 +1,98 @@
+use crate::cache::{Expiring, LoadFuture};
+use crate::error::CacheError;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock, Weak};
+
+/// A cache that holds weak references, so it never keeps a value alive on its own
+///
+/// The loader returns an `Arc<V>` and the cache only stores a `Weak<V>` to it.
+/// As long as something else holds the `Arc`, `get` hands out another strong
+/// reference to the same allocation. Once every `Arc` is dropped the entry
+/// reads as a miss and the value is loaded again. This memoizes shared objects
+/// without stopping them from being reclaimed.
+pub struct WeakCache<K, V, F>
+where
+    K: Hash + Eq + Clone,
+    F: Fn(K) -> LoadFuture<Arc<V>>,
+{
+    map: RwLock<HashMap<K, Expiring<Weak<V>>>>,
+    load: F,
+}
+
+impl<K, V, F> WeakCache<K, V, F>
+where
+    K: Hash + Eq + Clone + Send + Sync,
+    V: Send + Sync,
+    F: Fn(K) -> LoadFuture<Arc<V>>,
+{
+    /// Creates a new cache with the given loader
+    pub fn new(load: F) -> Self {
+        Self {
+            map: RwLock::new(HashMap::new()),
+            load,
+        }
+    }
+
+    /// Gets a value, loading it if it's missing, expired or was dropped everywhere else
+    ///
+    /// If the loader returns a value that has already expired, such as one with
+    /// a zero TTL, it's returned to the caller but not cached.
+    pub async fn get(&self, key: K) -> Result<Arc<V>, CacheError> {
+        let expiring = self.get_with_expiry(key).await?;
+        Ok(expiring.value)
+    }
+
+    /// Gets a value with its expiration information
+    pub async fn get_with_expiry(&self, key: K) -> Result<Expiring<Arc<V>>, CacheError> {
+        if let Ok(map) = self.map.read()
+            && let Some(item) = map.get(&key)
+            && !item.is_expired()
+            && let Some(value) = item.value.upgrade()
+        {
+            return Ok(item.as_ref().map(|_| value));
+        }
+
+        let item = (self.load)(key.clone()).await?;
+        if !item.remaining_ttl().is_zero() {
+            self.insert(key, &item);
+        }
+        Ok(item)
+    }
+
+    /// Caches a weak reference to `item`'s value, replacing any existing one for the key
+    pub fn insert(&self, key: K, item: &Expiring<Arc<V>>) {
+        if let Ok(mut map) = self.map.write() {
+            map.insert(key, item.as_ref().map(Arc::downgrade));
+        }
+    }
+
+    /// Deletes an item from the cache
+    pub fn delete(&self, key: &K) {
+        if let Ok(mut map) = self.map.write() {
+            map.remove(key);
+        }
+    }
+
+    /// Clears all items from the cache
+    pub fn delete_all(&self) {
+        if let Ok(mut map) = self.map.write() {
+            map.clear();
+        }
+    }
+
+    /// Removes expired items and ones whose value was dropped, returning how many were removed
+    pub fn purge_expired(&self) -> usize {
+        let Ok(mut map) = self.map.write() else {
+            return 0;
+        };
+        let before = map.len();
+        map.retain(|_, item| !item.is_expired() && item.value.strong_count() > 0);
+        before - map.len()
+    }
+
+    /// Gets the current size of the cache, including entries whose value was dropped
+    pub fn size(&self) -> usize {
+        self.map.read().map(|map| map.len()).unwrap_or(0)
+    }
+}
@@ -1,4 +1,4 @@
-use cache_rs::{Cache, Expiring};
+use cache_rs::{Cache, CacheError, Expiring};
 use std::time::Duration;
 
 #[tokio::test]
@@ -87,3 +87,1072 @@ async fn test_delete_operations() {
     cache.delete_all();
     assert_eq!(cache.size(), 0);
 }
+
+#[tokio::test]
+async fn test_sharded_storage() {
+    let cache = Cache::with_shards(
+        |key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(1)))
+            })
+        },
+        |key: &i32| key.to_string(),
+        4,
+    );
+    assert_eq!(cache.shard_count(), 4);
+
+    for key in 0..100 {
+        let result = cache.get(key).await.unwrap();
+        assert_eq!(result, format!("loaded_{}", key));
+    }
+    assert_eq!(cache.size(), 100);
+
+    cache.delete(42);
+    assert_eq!(cache.size(), 99);
+
+    cache.delete_all();
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_never_expiring_entries() {
+    let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+
+    let cache = Cache::new(
+        move |key: i32| {
+            let counter = counter_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let value = format!("loaded_{}", key);
+                if key == 0 {
+                    Ok(Expiring::never(value))
+                } else {
+                    Ok(Expiring::with_duration(value, Duration::from_millis(50)))
+                }
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    cache.get(0).await.unwrap();
+    cache.get(1).await.unwrap();
+    assert_eq!(cache.size(), 2);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert_eq!(cache.purge_expired(), 1);
+    assert_eq!(cache.size(), 1);
+
+    let result = cache.get(0).await.unwrap();
+    assert_eq!(result, "loaded_0");
+    assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_purge_expired_follows_moved_expiries() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+    let now = std::time::SystemTime::now();
+    cache.insert(1, Expiring::with_duration(1, Duration::from_millis(30)));
+    cache.insert(2, Expiring::with_duration(2, Duration::from_millis(30)));
+    cache.insert(3, Expiring::with_duration(3, Duration::from_secs(60)));
+    cache.insert(4, Expiring::never(4));
+    cache.insert(5, Expiring::with_duration(5, Duration::from_millis(30)));
+
+    // Pushed out, replaced, pulled in, given an expiry, or left to expire
+    assert!(cache.expire_at(&1, now + Duration::from_secs(60)));
+    cache.insert(2, Expiring::with_duration(20, Duration::from_secs(60)));
+    assert!(cache.expire_at(&3, now));
+    cache.retain(|identifier, item| {
+        if identifier == "4" {
+            item.expires_at = Some(now);
+        }
+        true
+    });
+    std::thread::sleep(Duration::from_millis(60));
+
+    assert_eq!(cache.purge_expired(), 3);
+    assert_eq!(cache.try_peek(&1), Some(1));
+    assert_eq!(cache.try_peek(&2), Some(20));
+    assert_eq!(cache.size(), 2);
+    assert_eq!(cache.purge_expired(), 0);
+}
+
+#[tokio::test]
+async fn test_get_or_insert_with() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                let value = key * 100;
+                Ok(Expiring::with_duration(value, Duration::from_secs(1)))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let runs = std::cell::Cell::new(0);
+    let compute = || {
+        runs.set(runs.get() + 1);
+        Ok::<_, std::num::ParseIntError>(6 * 7)
+    };
+
+    assert_eq!(
+        cache
+            .get_or_insert_with(1, Duration::from_secs(10), compute)
+            .await
+            .unwrap(),
+        42
+    );
+    assert_eq!(
+        cache
+            .get_or_insert_with(1, Duration::from_secs(10), compute)
+            .await
+            .unwrap(),
+        42
+    );
+    assert_eq!(runs.get(), 1);
+
+    // The computed value is shared with regular lookups
+    assert_eq!(cache.get(1).await.unwrap(), 42);
+
+    let failed = cache
+        .get_or_insert_with(2, Duration::from_secs(10), || "x".parse::<i32>())
+        .await;
+    assert!(failed.is_err());
+    assert_eq!(cache.size(), 1);
+
+    // An already-expired result is returned but not cached
+    let uncached = cache
+        .get_or_insert_with(3, Duration::ZERO, || Ok::<_, std::num::ParseIntError>(3))
+        .await;
+    assert_eq!(uncached.unwrap(), 3);
+    assert_eq!(cache.size(), 1);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses), (2, 3));
+}
+
+#[tokio::test]
+async fn test_get_cached_only() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                let value = key * 100;
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let missing = cache.get_cached_only(1).await;
+    assert!(matches!(missing, Err(CacheError::NotCached)));
+    assert_eq!(cache.size(), 0);
+
+    cache.get(1).await.unwrap();
+    assert_eq!(cache.get_cached_only(1).await.unwrap(), 100);
+
+    cache.insert(2, Expiring::with_duration(7, Duration::from_millis(10)));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(matches!(
+        cache.get_cached_only(2).await,
+        Err(CacheError::NotCached)
+    ));
+}
+
+#[tokio::test]
+async fn test_insert_many() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::with_shards(
+        move |key: i32| {
+            let loads = loads_clone.clone();
+            Box::pin(async move {
+                loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(Expiring::with_duration(key, Duration::from_secs(10)))
+            })
+        },
+        |key: &i32| key.to_string(),
+        4,
+    );
+
+    let batch = (0..50)
+        .map(|key| {
+            (
+                key,
+                Expiring::with_duration(key * 2, Duration::from_secs(10)),
+            )
+        })
+        .chain([(7, Expiring::never(-7))]);
+    cache.insert_many(batch);
+
+    // The duplicate key counts once and the later value wins
+    assert_eq!(cache.size(), 50);
+    assert_eq!(cache.get(7).await.unwrap(), -7);
+    for key in (0..50).filter(|key| *key != 7) {
+        assert_eq!(cache.get(key).await.unwrap(), key * 2);
+    }
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 0);
+}
+
+#[derive(Default)]
+struct FnvHasher(u64);
+
+impl std::hash::Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+type FnvBuildHasher = std::hash::BuildHasherDefault<FnvHasher>;
+
+#[tokio::test]
+async fn test_custom_hasher() {
+    let cache = Cache::with_hasher(
+        |key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        },
+        |key: &i32| key.to_string(),
+        FnvBuildHasher::default(),
+    );
+
+    for key in 0..20 {
+        assert_eq!(cache.get(key).await.unwrap(), format!("loaded_{}", key));
+    }
+    assert_eq!(cache.size(), 20);
+
+    cache.delete(3);
+    assert_eq!(cache.size(), 19);
+    assert!(cache.get_cached_only(3).await.is_err());
+    assert_eq!(cache.get_cached_only(4).await.unwrap(), "loaded_4");
+
+    let built = cache_rs::CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .with_hasher(FnvBuildHasher::default())
+        .shards(4)
+        .build();
+    assert_eq!(built.get(9).await.unwrap(), 9);
+    assert_eq!(built.size(), 1);
+}
+
+#[tokio::test]
+async fn test_clear_returns_removed_count() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+
+    for key in 0..25 {
+        cache.get(key).await.unwrap();
+    }
+    assert_eq!(cache.size(), 25);
+
+    assert_eq!(cache.clear(), 25);
+    assert_eq!(cache.size(), 0);
+    assert_eq!(cache.clear(), 0);
+}
+
+#[tokio::test]
+async fn test_expire_at() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+
+    assert!(!cache.expire_at(&1, std::time::SystemTime::now()));
+
+    cache.get(1).await.unwrap();
+    let when = std::time::SystemTime::now() + Duration::from_millis(50);
+    assert!(cache.expire_at(&1, when));
+
+    let item = cache.get_with_expiry(1).await.unwrap();
+    assert_eq!(item.expires_at, Some(when));
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert!(cache.get_cached_only(1).await.is_err());
+    assert_eq!(cache.active_size(), 0);
+
+    // A time in the past expires the entry immediately
+    cache.get(2).await.unwrap();
+    assert!(cache.expire_at(&2, std::time::SystemTime::now() - Duration::from_secs(1)));
+    assert!(cache.get_cached_only(2).await.is_err());
+}
+
+#[tokio::test]
+async fn test_update_in_place() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move { Ok(Expiring::with_duration(vec![key], Duration::from_secs(60))) })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    assert!(!cache.update(&1, |list| list.push(0)));
+    assert_eq!(cache.size(), 0);
+
+    let before = cache.get_with_expiry(1).await.unwrap();
+    assert!(cache.update(&1, |list| list.push(2)));
+
+    let after = cache.get_with_expiry(1).await.unwrap();
+    assert_eq!(after.value, vec![1, 2]);
+    assert_eq!(after.expires_at, before.expires_at);
+
+    // Expired entries aren't updated
+    cache.expire_at(&1, std::time::SystemTime::now() - Duration::from_secs(1));
+    assert!(!cache.update(&1, |list| list.push(3)));
+}
+
+#[tokio::test]
+async fn test_entry_handle_chains() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(vec![key])) }),
+        |key: &i32| key.to_string(),
+    );
+
+    // Absent: and_modify does nothing and or_insert_with inserts
+    let value = cache
+        .entry(1)
+        .unwrap()
+        .and_modify(|list| list.push(0))
+        .or_insert_with(Duration::from_secs(60), || vec![10]);
+    assert_eq!(value, vec![10]);
+    let ttl = cache.entry(1).unwrap().ttl().unwrap();
+    assert!(ttl > Duration::from_secs(50) && ttl <= Duration::from_secs(60));
+
+    // Present: and_modify changes it and or_insert_with keeps it
+    let value = cache
+        .entry(1)
+        .unwrap()
+        .and_modify(|list| list.push(11))
+        .or_insert_with(Duration::from_secs(60), || vec![99]);
+    assert_eq!(value, vec![10, 11]);
+
+    let entry = cache.entry(1).unwrap();
+    assert!(entry.set_ttl(Duration::from_secs(600)));
+    assert!(entry.ttl().unwrap() > Duration::from_secs(500));
+    assert!(!cache.entry(2).unwrap().set_ttl(Duration::from_secs(600)));
+    assert_eq!(cache.entry(2).unwrap().ttl(), None);
+}
+
+#[tokio::test]
+async fn test_unrepresentable_expiries_never_expire() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+    cache.insert(1, Expiring::never(1));
+    let entry = cache.entry(1).unwrap();
+    assert!(entry.set_ttl(entry.ttl().unwrap()));
+    assert_eq!(entry.ttl(), Some(Duration::MAX));
+
+    // Sliding by more than can be represented
+    let sliding = cache_rs::CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .sliding(Duration::MAX)
+        .build();
+    sliding.insert(1, Expiring::with_duration(1, Duration::from_secs(60)));
+    assert_eq!(sliding.get(1).await.unwrap(), 1);
+    assert_eq!(sliding.ttl_remaining(&1), Some(Duration::MAX));
+
+    // Reviving a stale value for more than can be represented
+    let stale = cache_rs::CacheBuilder::new()
+        .loader(|_key: i32| Box::pin(async move { Err("backend down".into()) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .on_reload_error(cache_rs::ReloadErrorPolicy::StaleFallback {
+            grace: Duration::MAX,
+        })
+        .build();
+    let past = std::time::SystemTime::now() - Duration::from_secs(1);
+    stale.insert(1, Expiring::new(1, past));
+    assert_eq!(stale.get(1).await.unwrap(), 1);
+    assert_eq!(stale.ttl_remaining(&1), Some(Duration::MAX));
+}
+
+#[test]
+fn test_entry_or_insert_with_runs_once_under_contention() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+    let calls = std::sync::atomic::AtomicUsize::new(0);
+    let barrier = std::sync::Barrier::new(8);
+
+    let values: Vec<i32> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let (cache, calls, barrier) = (&cache, &calls, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    cache
+                        .entry(1)
+                        .unwrap()
+                        .or_insert_with(Duration::from_secs(60), || {
+                            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(10));
+                            thread
+                        })
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert!(values.iter().all(|value| *value == values[0]));
+    assert_eq!(cache.try_peek(&1), Some(values[0]));
+}
+
+#[tokio::test]
+async fn test_expired_load_is_not_cached() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let loads = loads_clone.clone();
+            Box::pin(async move {
+                loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(Expiring::with_duration(key, Duration::ZERO))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(cache.size(), 0);
+
+    let past = std::time::SystemTime::now() - Duration::from_secs(1);
+    let stale = Cache::new(
+        move |key: i32| Box::pin(async move { Ok(Expiring::new(key, past)) }),
+        |key: &i32| key.to_string(),
+    );
+    assert_eq!(stale.get(2).await.unwrap(), 2);
+    assert_eq!(stale.size(), 0);
+
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_uncacheable_load() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let load = loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                // The first answer is degraded and mustn't be cached
+                if load == 1 {
+                    Ok(Expiring::no_store(format!("partial_{}", key)))
+                } else {
+                    Ok(Expiring::with_duration(
+                        format!("full_{}", key),
+                        Duration::from_secs(60),
+                    ))
+                }
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    assert_eq!(cache.get(1).await.unwrap(), "partial_1");
+    assert_eq!(cache.size(), 0);
+
+    assert_eq!(cache.get(1).await.unwrap(), "full_1");
+    assert_eq!(cache.get(1).await.unwrap(), "full_1");
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(cache.size(), 1);
+}
+
+struct LoadContext {
+    loads: std::sync::atomic::AtomicUsize,
+    prefix: &'static str,
+}
+
+#[tokio::test]
+async fn test_loader_with_context() {
+    let cache = Cache::with_context(
+        LoadContext {
+            loads: std::sync::atomic::AtomicUsize::new(0),
+            prefix: "user",
+        },
+        |key: i32, ctx: &LoadContext| {
+            let load = ctx.loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            let value = format!("{}_{}_{}", ctx.prefix, key, load);
+            Box::pin(async move { Ok(Expiring::with_duration(value, Duration::from_secs(10))) })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    // Every load sees the same context, so the counter keeps climbing
+    assert_eq!(cache.get(1).await.unwrap(), "user_1_1");
+    assert_eq!(cache.get(2).await.unwrap(), "user_2_2");
+    assert_eq!(cache.get(1).await.unwrap(), "user_1_1");
+    cache.delete(1);
+    assert_eq!(cache.get(1).await.unwrap(), "user_1_3");
+}
+
+/// Hashes like the default hasher, but once armed parks the second hash it
+/// performs, which for an insert happens while the shard's write lock is held
+#[derive(Clone, Default)]
+struct GatedBuildHasher(std::sync::Arc<Gate>);
+
+#[derive(Default)]
+struct Gate {
+    armed: std::sync::atomic::AtomicBool,
+    hashes: std::sync::atomic::AtomicUsize,
+    entered: std::sync::OnceLock<std::sync::Barrier>,
+    release: std::sync::OnceLock<std::sync::Barrier>,
+}
+
+impl Gate {
+    fn entered(&self) -> &std::sync::Barrier {
+        self.entered.get_or_init(|| std::sync::Barrier::new(2))
+    }
+
+    fn release(&self) -> &std::sync::Barrier {
+        self.release.get_or_init(|| std::sync::Barrier::new(2))
+    }
+}
+
+struct GatedHasher(std::sync::Arc<Gate>, std::hash::DefaultHasher);
+
+impl std::hash::Hasher for GatedHasher {
+    fn finish(&self) -> u64 {
+        let gate = &self.0;
+        if gate.armed.load(std::sync::atomic::Ordering::SeqCst)
+            && gate
+                .hashes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                == 1
+        {
+            gate.entered().wait();
+            gate.release().wait();
+        }
+        self.1.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.1.write(bytes);
+    }
+}
+
+impl std::hash::BuildHasher for GatedBuildHasher {
+    type Hasher = GatedHasher;
+
+    fn build_hasher(&self) -> GatedHasher {
+        GatedHasher(self.0.clone(), std::hash::DefaultHasher::new())
+    }
+}
+
+#[test]
+fn test_try_peek_does_not_block_on_writer() {
+    let gate = GatedBuildHasher::default();
+    let cache = cache_rs::CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .with_hasher(gate.clone())
+        .shards(1)
+        .build();
+
+    assert_eq!(cache.try_peek(&1), None);
+    cache.insert(1, Expiring::never(10));
+    assert_eq!(cache.try_peek(&1), Some(10));
+
+    gate.0
+        .armed
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+    std::thread::scope(|scope| {
+        scope.spawn(|| cache.insert(2, Expiring::never(20)));
+
+        // The insert is now parked inside the shard's write lock
+        gate.0.entered().wait();
+        let started = std::time::Instant::now();
+        assert_eq!(cache.try_peek(&1), None);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        gate.0.release().wait();
+    });
+    gate.0
+        .armed
+        .store(false, std::sync::atomic::Ordering::SeqCst);
+
+    assert_eq!(cache.try_peek(&1), Some(10));
+    assert_eq!(cache.try_peek(&2), Some(20));
+}
+
+/// Key that counts how many times it has been cloned
+struct CountingKey {
+    id: i32,
+    clones: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl Clone for CountingKey {
+    fn clone(&self) -> Self {
+        self.clones
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Self {
+            id: self.id,
+            clones: self.clones.clone(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_get_ref_only_clones_on_miss() {
+    let cache = Cache::new(
+        |key: CountingKey| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key.id);
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        },
+        |key: &CountingKey| key.id.to_string(),
+    );
+
+    let clones = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let key = CountingKey {
+        id: 7,
+        clones: clones.clone(),
+    };
+
+    assert_eq!(cache.get_ref(&key).await.unwrap(), "loaded_7");
+    assert_eq!(clones.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    for _ in 0..5 {
+        assert_eq!(cache.get_ref(&key).await.unwrap(), "loaded_7");
+    }
+    assert_eq!(clones.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_get_many() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                if key < 0 {
+                    return Err("negative key".into());
+                }
+                Ok(Expiring::never(key * 10))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+    cache.insert(2, Expiring::never(-1));
+
+    let started = std::time::Instant::now();
+    let results = cache.get_many(vec![3, 1, 2, 3, -1, -1]).await;
+
+    // Missing keys load concurrently, and repeated keys load once
+    assert!(started.elapsed() < Duration::from_millis(120));
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    let values: Vec<_> = results[..4]
+        .iter()
+        .map(|result| *result.as_ref().unwrap())
+        .collect();
+    assert_eq!(values, vec![30, 10, -1, 30]);
+    for result in &results[4..] {
+        let error = result.as_ref().unwrap_err();
+        assert!(matches!(error, CacheError::Load(inner) if inner.is::<cache_rs::SharedError>()));
+        assert_eq!(error.to_string(), "negative key");
+    }
+}
+
+#[tokio::test]
+async fn test_get_map() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                if key < 0 {
+                    return Err("negative key".into());
+                }
+                Ok(Expiring::never(key * 10))
+            })
+        },
+        |key: &i32| format!("key{}", key),
+    );
+    cache.insert(2, Expiring::never(-1));
+
+    let results = cache.get_map(vec![3, 1, 2, 3, -1, -1]).await;
+
+    assert_eq!(results.len(), 4);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert_eq!(*results["key1"].as_ref().unwrap(), 10);
+    assert_eq!(*results["key2"].as_ref().unwrap(), -1);
+    assert_eq!(*results["key3"].as_ref().unwrap(), 30);
+    assert_eq!(
+        results["key-1"].as_ref().unwrap_err().to_string(),
+        "negative key"
+    );
+}
+
+#[tokio::test]
+async fn test_warm() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                if key == 4 {
+                    return Err("no such key".into());
+                }
+                Ok(Expiring::never(key * 10))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+    cache.insert(1, Expiring::never(-1));
+
+    let results = cache.warm([1, 2, 3, 4]).await;
+    assert!(results[..3].iter().all(Result::is_ok));
+    assert_eq!(results[3].as_ref().unwrap_err().to_string(), "no such key");
+
+    // The already-cached key was skipped and the rest are now hits
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert_eq!(cache.try_peek(&1), Some(-1));
+    assert_eq!(cache.try_peek(&2), Some(20));
+    assert_eq!(cache.try_peek(&3), Some(30));
+}
+
+#[tokio::test]
+async fn test_refresh_compare() {
+    let version = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(1));
+    let version_clone = version.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let version = version_clone.load(std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::never(format!("{key}_v{version}"))) })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    // Nothing cached yet
+    assert_eq!(
+        cache.refresh_compare(1).await.unwrap(),
+        (None, "1_v1".to_string())
+    );
+
+    version.store(2, std::sync::atomic::Ordering::SeqCst);
+    let (old, new) = cache.refresh_compare(1).await.unwrap();
+    assert_eq!(old.as_deref(), Some("1_v1"));
+    assert_eq!(new, "1_v2");
+    assert_eq!(cache.get(1).await.unwrap(), "1_v2");
+
+    // An expired entry still counts as the previous value
+    cache.insert(
+        2,
+        Expiring::new("stale".to_string(), std::time::SystemTime::UNIX_EPOCH),
+    );
+    let (old, new) = cache.refresh_compare(2).await.unwrap();
+    assert_eq!(old.as_deref(), Some("stale"));
+    assert_eq!(new, "2_v2");
+}
+
+#[tokio::test]
+async fn test_refresh_compare_goes_through_the_load_path() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let version = loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                Ok(Expiring::never(format!("{key}_v{version}")))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    // A concurrent get and refresh share one loader call
+    let (loaded, refreshed) = tokio::join!(cache.get(1), cache.refresh_compare(1));
+    assert_eq!(loaded.unwrap(), "1_v1");
+    assert_eq!(refreshed.unwrap(), (None, "1_v1".to_string()));
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // A write made while the reload runs wins over its result
+    let (refreshed, _) = tokio::join!(cache.refresh_compare(1), async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache.insert(1, Expiring::never("written".to_string()));
+    });
+    assert_eq!(refreshed.unwrap().1, "written");
+    assert_eq!(cache.try_peek(&1).as_deref(), Some("written"));
+
+    // So does a clear
+    let (refreshed, _) = tokio::join!(cache.refresh_compare(1), async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache.clear();
+    });
+    assert_eq!(refreshed.unwrap().1, "1_v3");
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_retain_filters_and_extends() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+    for key in 1..=6 {
+        cache.insert(key, Expiring::with_duration(key, Duration::from_millis(50)));
+    }
+
+    let removed = cache.retain(|identifier, item| {
+        let keep = identifier.parse::<i32>().unwrap() % 2 == 0;
+        if keep {
+            item.expires_at = Some(std::time::SystemTime::now() + Duration::from_secs(60));
+        }
+        keep
+    });
+    assert_eq!(removed, 3);
+    assert_eq!(cache.size(), 3);
+
+    // The kept entries outlive their original TTL
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(cache.active_size(), 3);
+    assert_eq!(cache.try_peek(&2), Some(2));
+    assert_eq!(cache.try_peek(&1), None);
+}
+
+#[tokio::test]
+async fn test_get_accept_tolerates_staleness() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let version = loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                Ok(Expiring::with_duration(
+                    format!("{}_v{}", key, version),
+                    Duration::from_millis(30),
+                ))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+    let up_to_200ms = |_: &String, age: Duration| age < Duration::from_millis(200);
+
+    assert_eq!(cache.get_accept(1, up_to_200ms).await.unwrap(), "1_v1");
+
+    // Expired, but young enough for this caller
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(cache.get_accept(1, up_to_200ms).await.unwrap(), "1_v1");
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // A stricter caller reloads
+    assert_eq!(cache.get_accept(1, |_, _| false).await.unwrap(), "1_v2");
+
+    // Too old even for the tolerant caller
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(cache.get_accept(1, up_to_200ms).await.unwrap(), "1_v3");
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_delete_all_discards_in_flight_loads() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(60)).await;
+                Ok(Expiring::never(key))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (loaded, ()) = tokio::join!(cache.get(1), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache.delete_all();
+    });
+
+    // The caller still gets its value, but the cleared cache stays empty
+    assert_eq!(loaded.unwrap(), 1);
+    assert_eq!(cache.size(), 0);
+    assert_eq!(cache.try_peek(&1), None);
+
+    // Loads started after the clear are cached as usual
+    cache.get(1).await.unwrap();
+    assert_eq!(cache.try_peek(&1), Some(1));
+}
+
+#[tokio::test]
+async fn test_get_or_load_with_populates_shared_cache() {
+    let counter = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let counter = counter_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(Expiring::never(format!("primary_{}", key)))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let value = cache
+        .get_or_load_with(7, |key| async move {
+            Ok::<_, std::io::Error>(Expiring::never(format!("fallback_{}", key)))
+        })
+        .await
+        .unwrap();
+    assert_eq!(value, "fallback_7");
+
+    // Normal gets hit the value the override loaded
+    assert_eq!(cache.get(7).await.unwrap(), "fallback_7");
+    assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    // A hit never calls the override
+    let value = cache
+        .get_or_load_with(7, |_| async {
+            Ok::<_, std::io::Error>(Expiring::never("replaced".to_string()))
+        })
+        .await;
+    assert_eq!(value.unwrap(), "fallback_7");
+
+    // Override errors are returned and nothing is cached
+    let result = cache
+        .get_or_load_with(8, |_| async {
+            Err::<Expiring<String>, _>(std::io::Error::other("fallback down"))
+        })
+        .await;
+    assert!(matches!(result, Err(CacheError::Load(_))));
+    assert_eq!(cache.get(8).await.unwrap(), "primary_8");
+}
+
+#[tokio::test]
+async fn test_get_batch_loads_misses_in_one_call() {
+    let batches = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let batches_clone = batches.clone();
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(format!("single_{}", key))) }),
+        |key: &i32| key.to_string(),
+    )
+    .with_batch_loader(move |keys: Vec<i32>| {
+        batches_clone.lock().unwrap().push(keys.clone());
+        Box::pin(async move {
+            // Key 13 has no value in the backend
+            Ok(keys
+                .into_iter()
+                .filter(|key| *key != 13)
+                .map(|key| (key, Expiring::never(format!("batch_{}", key))))
+                .collect())
+        })
+    });
+    cache.insert(2, Expiring::never("cached_2".to_string()));
+
+    let results = cache.get_batch(vec![1, 2, 3, 13, 1]).await;
+    assert_eq!(results[0].as_ref().unwrap(), "batch_1");
+    assert_eq!(results[1].as_ref().unwrap(), "cached_2");
+    assert_eq!(results[2].as_ref().unwrap(), "batch_3");
+    assert!(matches!(results[3], Err(CacheError::Load(_))));
+    assert_eq!(results[4].as_ref().unwrap(), "batch_1");
+    assert_eq!(*batches.lock().unwrap(), vec![vec![1, 3, 13]]);
+
+    // Batch-loaded values are cached for every lookup
+    assert_eq!(cache.get(3).await.unwrap(), "batch_3");
+    let results = cache.get_batch(vec![1, 3]).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(batches.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_delete_await_removes_in_flight_result() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Expiring::never(key))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (loaded, _) = tokio::join!(cache.get(1), async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache.delete_await(1).await;
+        assert_eq!(cache.try_peek(&1), None);
+    });
+
+    // The caller that was loading still gets its value, but it isn't kept
+    assert_eq!(loaded.unwrap(), 1);
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_clones_share_the_cache() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::never(key * 10)) })
+        },
+        |key: &i32| key.to_string(),
+    );
+    let handle = cache.clone();
+
+    cache.insert(1, Expiring::never(100));
+    assert_eq!(handle.get(1).await.unwrap(), 100);
+
+    assert_eq!(handle.get(2).await.unwrap(), 20);
+    assert_eq!(cache.get(2).await.unwrap(), 20);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    handle.delete(1);
+    assert!(cache.try_peek(&1).is_none());
+    assert_eq!(cache.stats().hits, handle.stats().hits);
+}
+
+#[tokio::test]
+async fn test_reload_all_refreshes_every_entry() {
+    let version = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(1));
+    let version_clone = version.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let version = version_clone.load(std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::never(format!("v{}_{}", version, key))) })
+        },
+        |key: &i32| key.to_string(),
+    )
+    .remember_keys();
+
+    for key in 1..=3 {
+        cache.get(key).await.unwrap();
+    }
+    cache.insert(4, Expiring::never("inserted".to_string()));
+    version.store(2, std::sync::atomic::Ordering::SeqCst);
+
+    let mut results = cache.reload_all().await;
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    let identifiers: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(identifiers, vec!["1", "2", "3", "4"]);
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+    for key in 1..=4 {
+        assert_eq!(cache.get(key).await.unwrap(), format!("v2_{}", key));
+    }
+}
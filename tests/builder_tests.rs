@@ -0,0 +1,577 @@
+use cache_rs::{Cache, CacheBuilder, EvictionPolicy, Expiring};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+#[tokio::test]
+async fn test_builder_basic() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(1)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .shards(4)
+        .build();
+
+    assert_eq!(cache.shard_count(), 4);
+    assert_eq!(cache.get(7).await.unwrap(), "loaded_7");
+    assert_eq!(cache.size(), 1);
+
+    let options = cache.get_config().options;
+    assert_eq!(options.shard_count, 4);
+    assert_eq!(options.max_capacity, None);
+}
+
+#[tokio::test]
+async fn test_max_capacity_evicts_least_recently_used() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let counter = counter_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .max_capacity(2)
+        .build();
+
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    cache.get(1).await.unwrap();
+    cache.get(3).await.unwrap();
+    assert_eq!(cache.size(), 2);
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+
+    // Key 1 was used more recently than key 2, so key 2 was evicted
+    cache.get(1).await.unwrap();
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+    cache.get(2).await.unwrap();
+    assert_eq!(counter.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_sliding_expiration() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let counter = counter_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_millis(100)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .sliding(Duration::from_millis(100))
+        .build();
+
+    cache.get(1).await.unwrap();
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cache.get(1).await.unwrap();
+    }
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    cache.get(1).await.unwrap();
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_max_age_caps_sliding_expiration() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let counter = counter_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_millis(100)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .sliding(Duration::from_millis(100))
+        .max_age(Duration::from_millis(300))
+        .build();
+
+    // Hits keep extending the expiry, but never past 300ms after the load
+    cache.get(1).await.unwrap();
+    for _ in 0..5 {
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        cache.get(1).await.unwrap();
+    }
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        cache.get(1).await.unwrap();
+    }
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+    // Values that would never expire are capped too
+    cache.insert(2, Expiring::never("forever".to_string()));
+    let item = cache.get_with_expiry(2).await.unwrap();
+    assert!(item.remaining_ttl() <= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_put_uses_default_ttl() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(1)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .default_ttl(Duration::from_millis(50))
+        .build();
+
+    cache.put(1, "manual".to_string());
+    assert_eq!(cache.get(1).await.unwrap(), "manual");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(cache.get(1).await.unwrap(), "loaded_1");
+}
+
+#[tokio::test]
+async fn test_weigher_evicts_by_total_weight() {
+    let cache = CacheBuilder::new()
+        .loader(|key: usize| {
+            Box::pin(async move {
+                let value = "x".repeat(key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        })
+        .key_mapper(|key: &usize| key.to_string())
+        .with_weigher(|value: &String| value.len(), 10)
+        .build();
+
+    cache.get(4).await.unwrap();
+    cache.get(5).await.unwrap();
+    assert_eq!(cache.total_weight(), 9);
+    assert_eq!(cache.size(), 2);
+
+    // 4 + 5 + 3 = 12 > 10, so the least recently used entry (4) goes
+    cache.get(3).await.unwrap();
+    assert_eq!(cache.total_weight(), 8);
+    let mut keys = cache.keys();
+    keys.sort();
+    assert_eq!(keys, vec!["3", "5"]);
+
+    // Heavier than the whole budget: returned but never cached
+    let heavy = cache.get(20).await.unwrap();
+    assert_eq!(heavy.len(), 20);
+    assert_eq!(cache.size(), 2);
+    assert_eq!(cache.total_weight(), 8);
+}
+
+fn jittered_expiries(base: SystemTime, seed: u64) -> Vec<Duration> {
+    let cache = CacheBuilder::new()
+        .loader(move |key: u32| Box::pin(async move { Ok(Expiring::new(key, base)) }))
+        .key_mapper(|key: &u32| key.to_string())
+        .with_jitter(Duration::from_secs(10))
+        .jitter_seed(seed)
+        .build();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    (0..100)
+        .map(|key| {
+            let item = runtime.block_on(cache.get_with_expiry(key)).unwrap();
+            item.expires_at.unwrap().duration_since(base).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_jitter_spreads_expiries() {
+    let base = SystemTime::now() + Duration::from_secs(60);
+    let offsets = jittered_expiries(base, 7);
+
+    assert!(
+        offsets
+            .iter()
+            .all(|offset| *offset < Duration::from_secs(10))
+    );
+    let min = offsets.iter().min().unwrap();
+    let max = offsets.iter().max().unwrap();
+    assert!(*max - *min > Duration::from_secs(5));
+
+    let mut distinct = offsets.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), offsets.len());
+
+    // The same seed reproduces the same offsets
+    assert_eq!(jittered_expiries(base, 7), offsets);
+    assert_ne!(jittered_expiries(base, 8), offsets);
+}
+
+#[tokio::test]
+async fn test_lfu_evicts_least_frequently_used() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .max_capacity(3)
+        .eviction_policy(EvictionPolicy::Lfu)
+        .build();
+
+    // Key 1 is hot, key 2 is touched once, and most recently of the two
+    cache.get(1).await.unwrap();
+    for _ in 0..10 {
+        cache.get(1).await.unwrap();
+    }
+    cache.get(2).await.unwrap();
+    cache.get(2).await.unwrap();
+    cache.get(3).await.unwrap();
+    for _ in 0..3 {
+        cache.get(3).await.unwrap();
+    }
+
+    // LRU would evict key 1 here; LFU drops the rarely used key 2
+    cache.get(4).await.unwrap();
+    let mut keys = cache.keys();
+    keys.sort();
+    assert_eq!(keys, vec!["1", "3", "4"]);
+}
+
+#[tokio::test]
+async fn test_update_reweighs_entry() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(vec![key])) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .with_weigher(|list: &Vec<i32>| list.len(), 3)
+        .build();
+
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    assert_eq!(cache.total_weight(), 2);
+
+    // Growing entry 2 past the limit evicts entry 1 rather than itself
+    assert!(cache.update(&2, |list| list.extend([20, 21])));
+    assert_eq!(cache.total_weight(), 3);
+    assert_eq!(cache.try_peek(&1), None);
+    assert_eq!(cache.try_peek(&2), Some(vec![2, 20, 21]));
+}
+
+#[tokio::test]
+async fn test_max_concurrent_loads() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let (running_clone, peak_clone) = (running.clone(), peak.clone());
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let (running, peak) = (running_clone.clone(), peak_clone.clone());
+            Box::pin(async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(Expiring::never(key))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .max_concurrent_loads(2)
+        .build();
+
+    let results = cache.get_many((0..6).collect()).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.size(), 6);
+}
+
+#[tokio::test]
+async fn test_namespaces() {
+    let namespaced = |namespace: &str| {
+        CacheBuilder::new()
+            .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+            .key_mapper(|key: &i32| key.to_string())
+            .namespace(namespace)
+            .build()
+    };
+    let users = namespaced("users");
+    let orders = namespaced("orders");
+
+    users.get(1).await.unwrap();
+    users.get(2).await.unwrap();
+    orders.get(1).await.unwrap();
+    assert_eq!(users.keys().len(), 2);
+    assert!(users.keys().iter().all(|id| id.starts_with("users:")));
+
+    // Entries restored from another namespace aren't part of this one
+    let mut foreign = orders.dump();
+    assert_eq!(foreign.entries[0].identifier, "orders:1");
+    foreign.entries[0].value = 100;
+    users.restore(foreign);
+
+    assert_eq!(users.invalidate_namespace(), 2);
+    assert_eq!(users.keys(), vec!["orders:1".to_string()]);
+    assert_eq!(orders.size(), 1);
+    assert_eq!(orders.get(1).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_clock_granularity_still_expires() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let counter = loads.clone();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_millis(50))) })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .clock_granularity(Duration::from_millis(20))
+        .build();
+
+    cache.get(1).await.unwrap();
+    cache.get(1).await.unwrap();
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // Past the TTL plus one granularity, the cached clock has caught up
+    tokio::time::sleep(Duration::from_millis(50 + 20 + 30)).await;
+    assert_eq!(cache.active_size(), 0);
+    cache.get(1).await.unwrap();
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_cache_from_config() {
+    let template = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(format!("loaded_{}", key))) }))
+        .key_mapper(|key: &i32| format!("k{}", key))
+        .max_capacity(2)
+        .build();
+    template.get(1).await.unwrap();
+
+    let tenant = Cache::from_config(template.to_config());
+    assert_eq!(tenant.size(), 0);
+    assert_eq!(tenant.get(7).await.unwrap(), "loaded_7");
+    assert_eq!(tenant.keys(), vec!["k7".to_string()]);
+    assert_eq!(tenant.get_config().options.max_capacity, Some(2));
+
+    // A borrowed config works too, as long as the template outlives the new cache
+    let borrowed = Cache::from_config(template.get_config());
+    assert_eq!(borrowed.get(7).await.unwrap(), "loaded_7");
+    assert_eq!(template.size(), 1);
+}
+
+#[tokio::test]
+async fn test_transform_runs_once_per_load() {
+    let transforms = Arc::new(AtomicUsize::new(0));
+    let transforms_clone = transforms.clone();
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| {
+            Box::pin(async move {
+                let value = format!("value_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_millis(50)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .transform(move |value: String| {
+            transforms_clone.fetch_add(1, Ordering::SeqCst);
+            value.to_uppercase()
+        })
+        .build();
+
+    assert_eq!(cache.get(1).await.unwrap(), "VALUE_1");
+    assert_eq!(cache.get(1).await.unwrap(), "VALUE_1");
+    assert_eq!(transforms.load(Ordering::SeqCst), 1);
+
+    // Direct inserts are stored as given
+    cache.insert(2, Expiring::never("manual".to_string()));
+    assert_eq!(cache.get(2).await.unwrap(), "manual");
+    assert_eq!(transforms.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(cache.get(1).await.unwrap(), "VALUE_1");
+    assert_eq!(transforms.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_initial_capacity_behaves_like_default() {
+    let cache = Cache::with_initial_capacity(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key * 2)) }),
+        |key: &i32| key.to_string(),
+        10_000,
+    );
+    for key in 0..5_000 {
+        cache.insert(key, Expiring::never(key));
+    }
+    assert_eq!(cache.size(), 5_000);
+    assert_eq!(cache.get(4_999).await.unwrap(), 4_999);
+    assert_eq!(cache.get(5_000).await.unwrap(), 10_000);
+
+    // Initial capacity doesn't raise or replace the entry limit
+    let bounded = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .initial_capacity(1_000)
+        .max_capacity(100)
+        .build();
+    for key in 0..500 {
+        bounded.get(key).await.unwrap();
+    }
+    assert_eq!(bounded.size(), 100);
+    assert_eq!(bounded.capacity(), Some(100));
+}
+
+#[tokio::test]
+async fn test_adaptive_ttl_extends_hot_keys() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| {
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_millis(100))) })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .adaptive_ttl(|accesses, base_ttl| base_ttl * (1 + accesses as u32))
+        .build();
+
+    cache.get(1).await.unwrap();
+    for _ in 0..9 {
+        cache.get(1).await.unwrap();
+    }
+    cache.get(2).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    // Reloads are cached for the base TTL times one plus the previous hits
+    let hot = cache.get_with_expiry(1).await.unwrap().remaining_ttl();
+    let cold = cache.get_with_expiry(2).await.unwrap().remaining_ttl();
+    assert!(hot > Duration::from_millis(500), "hot TTL was {:?}", hot);
+    assert!(
+        cold <= Duration::from_millis(100),
+        "cold TTL was {:?}",
+        cold
+    );
+}
+
+#[tokio::test]
+async fn test_adaptive_ttl_counts_hits_across_reloads() {
+    let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen_clone = seen.clone();
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| {
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_secs(60))) })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .eviction_policy(EvictionPolicy::Lfu)
+        .adaptive_ttl(move |accesses, base_ttl| {
+            seen_clone.lock().unwrap().push(accesses);
+            base_ttl
+        })
+        .build();
+
+    cache.get(1).await.unwrap();
+    for _ in 0..3 {
+        cache.get(1).await.unwrap();
+    }
+    cache.refresh_compare(1).await.unwrap();
+    for _ in 0..2 {
+        cache.get(1).await.unwrap();
+    }
+    cache.refresh_compare(1).await.unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), vec![0, 3, 5]);
+}
+
+#[tokio::test]
+async fn test_expiry_tolerance_absorbs_tiny_ttls() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let build = |tolerance: Duration| {
+        let loads = loads.clone();
+        CacheBuilder::new()
+            .loader(move |key: i32| {
+                loads.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move { Ok(Expiring::never(key)) })
+            })
+            .key_mapper(|key: &i32| key.to_string())
+            .expiry_tolerance(tolerance)
+            .build()
+    };
+    let tolerant = build(Duration::from_millis(200));
+    let strict = build(Duration::ZERO);
+    for cache in [&tolerant, &strict] {
+        cache.insert(1, Expiring::with_duration(100, Duration::from_micros(10)));
+    }
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    assert!(strict.try_peek(&1).is_none());
+    assert_eq!(tolerant.try_peek(&1), Some(100));
+    assert_eq!(tolerant.get(1).await.unwrap(), 100);
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(tolerant.try_peek(&1).is_none());
+    assert_eq!(tolerant.get(1).await.unwrap(), 1);
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_expiry_tolerance_applies_to_purge_and_update() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .expiry_tolerance(Duration::from_millis(200))
+        .build();
+    cache.insert(1, Expiring::with_duration(100, Duration::from_micros(10)));
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    // Past its expiry but within the tolerance, the entry is still live everywhere
+    assert_eq!(cache.purge_expired(), 0);
+    assert!(cache.update(&1, |value| *value += 1));
+    assert_eq!(cache.try_peek(&1), Some(101));
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(!cache.update(&1, |value| *value += 1));
+    assert_eq!(cache.purge_expired(), 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[test]
+fn test_refresh_ahead_clamps_thresholds_outside_unit_interval() {
+    let threshold_of = |threshold: f64| {
+        CacheBuilder::new()
+            .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+            .key_mapper(|key: &i32| key.to_string())
+            .refresh_ahead(threshold)
+            .build()
+            .get_config()
+            .options
+            .refresh_ahead
+    };
+
+    assert_eq!(threshold_of(0.5), Some(0.5));
+    assert_eq!(threshold_of(1.0), Some(1.0));
+    assert_eq!(threshold_of(1.5), Some(1.0));
+    assert_eq!(threshold_of(f64::INFINITY), Some(1.0));
+    for threshold in [0.0, -0.5, f64::NAN] {
+        assert_eq!(threshold_of(threshold), None, "kept {}", threshold);
+    }
+
+    // Options passed straight to a config are clamped the same way
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .build();
+    let mut config = cache.to_config();
+    config.options.refresh_ahead = Some(-1.0);
+    let cache = Cache::from_config(config);
+    assert_eq!(cache.get_config().options.refresh_ahead, None);
+}
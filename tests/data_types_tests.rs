@@ -1,5 +1,6 @@
 use cache_rs::{Cache, Expiring};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,6 +18,22 @@ struct Product {
     in_stock: bool,
 }
 
+/// A value that counts how often it's cloned
+#[derive(Debug)]
+struct CloneCounter {
+    clones: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl Clone for CloneCounter {
+    fn clone(&self) -> Self {
+        self.clones
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Self {
+            clones: self.clones.clone(),
+        }
+    }
+}
+
 #[tokio::test]
 async fn test_string_cache() {
     let cache = Cache::new(
@@ -170,3 +187,193 @@ async fn test_result_values() {
     let negative_result = cache.get(-1).await.unwrap();
     assert_eq!(negative_result, Err("negative_number".to_string()));
 }
+
+/// Loads even keys, reports odd keys as absent and fails on negative ones
+fn even_loader(
+    loads: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) -> impl Fn(i32) -> cache_rs::OptionalLoadFuture<String> {
+    move |key: i32| {
+        loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Box::pin(async move {
+            match key {
+                k if k < 0 => Err("negative key".into()),
+                k if k % 2 == 0 => Ok(Some(Expiring::with_duration(
+                    format!("even_{}", k),
+                    Duration::from_secs(10),
+                ))),
+                _ => Ok(None),
+            }
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_optional_present_and_absent() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = Cache::optional(
+        even_loader(loads.clone()),
+        |key: &i32| key.to_string(),
+        Some(Duration::from_secs(10)),
+    );
+
+    assert_eq!(
+        cache.get_optional(4).await.unwrap(),
+        Some("even_4".to_string())
+    );
+    assert_eq!(cache.get_optional(3).await.unwrap(), None);
+
+    // Both the value and the absence are cached
+    assert_eq!(
+        cache.get_optional(4).await.unwrap(),
+        Some("even_4".to_string())
+    );
+    assert_eq!(cache.get_optional(3).await.unwrap(), None);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(cache.size(), 2);
+}
+
+#[tokio::test]
+async fn test_optional_absence_without_ttl_is_not_cached() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = Cache::optional(
+        even_loader(loads.clone()),
+        |key: &i32| key.to_string(),
+        None,
+    );
+
+    assert_eq!(cache.get_optional(3).await.unwrap(), None);
+    assert_eq!(cache.get_optional(3).await.unwrap(), None);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_optional_loader_error() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = Cache::optional(
+        even_loader(loads),
+        |key: &i32| key.to_string(),
+        Some(Duration::from_secs(10)),
+    );
+
+    let error = cache.get_optional(-1).await.unwrap_err();
+    assert_eq!(error.to_string(), "negative key");
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_get_many_arc_shares_allocations() {
+    let cache = Cache::new(
+        |key: u8| Box::pin(async move { Ok(Expiring::never(Arc::new(vec![key; 1 << 20]))) }),
+        |key: &u8| key.to_string(),
+    );
+
+    let first = cache.get_arc(1).await.unwrap();
+    let batch = cache.get_many_arc(vec![1, 2, 1]).await;
+    let batch: Vec<Arc<Vec<u8>>> = batch.into_iter().map(Result::unwrap).collect();
+
+    assert!(Arc::ptr_eq(&first, &batch[0]));
+    assert!(Arc::ptr_eq(&first, &batch[2]));
+    assert_eq!(batch[1][0], 2);
+    assert!(Arc::ptr_eq(&batch[1], &cache.get_arc(2).await.unwrap()));
+}
+
+#[tokio::test]
+async fn test_metadata_travels_with_value() {
+    let cache = Cache::with_meta(
+        |key: u32| {
+            Box::pin(async move {
+                let item =
+                    Expiring::with_duration(format!("body_{}", key), Duration::from_secs(60));
+                Ok((item, format!("\"etag-{}\"", key)))
+            })
+        },
+        |key: &u32| key.to_string(),
+    );
+
+    let (body, etag) = cache.get_with_meta(7).await.unwrap();
+    assert_eq!(body, "body_7");
+    assert_eq!(etag, "\"etag-7\"");
+
+    // Revalidation replaces the ETag; the value's own expiry still applies
+    cache.insert_with_meta(
+        7,
+        Expiring::with_duration("body_7b".to_string(), Duration::from_millis(50)),
+        "\"etag-7b\"".to_string(),
+    );
+    let (body, etag) = cache.get_with_meta(7).await.unwrap();
+    assert_eq!((body.as_str(), etag.as_str()), ("body_7b", "\"etag-7b\""));
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(cache.get_with_meta(7).await.unwrap().1, "\"etag-7\"");
+}
+
+/// Deliberately not `Clone`, like a value owning a file handle
+struct Handle {
+    id: u32,
+}
+
+#[tokio::test]
+async fn test_shared_caches_non_clone_values() {
+    let cache = Cache::shared(
+        |key: u32| Box::pin(async move { Ok(Expiring::never(Handle { id: key })) }),
+        |key: &u32| key.to_string(),
+    );
+
+    let first: Arc<Handle> = cache.get(3).await.unwrap();
+    let second = cache.get_arc(3).await.unwrap();
+    assert_eq!(first.id, 3);
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(cache.size(), 1);
+}
+
+#[tokio::test]
+async fn test_get_mapped_projects_field() {
+    let loads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |user_id: u32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                let user = User {
+                    id: user_id,
+                    name: format!("User{}", user_id),
+                    email: format!("user{}@example.com", user_id),
+                };
+                Ok(Expiring::with_duration(user, Duration::from_secs(10)))
+            })
+        },
+        |key: &u32| key.to_string(),
+    );
+
+    // The miss loads the whole user and projects it
+    let name = cache.get_mapped(7, |user| user.name.clone()).await.unwrap();
+    assert_eq!(name, "User7");
+    // Hits project the cached user in place
+    let email_len = cache.get_mapped(7, |user| user.email.len()).await.unwrap();
+    assert_eq!(email_len, "user7@example.com".len());
+    assert_eq!(cache.get(7).await.unwrap().id, 7);
+
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert_eq!(cache.stats().hits, 2);
+}
+
+#[tokio::test]
+async fn test_fresh_load_clones_value_once() {
+    let clones = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let clones_clone = clones.clone();
+    let cache = Cache::new(
+        move |_key: i32| {
+            let clones = clones_clone.clone();
+            Box::pin(async move { Ok(Expiring::never(CloneCounter { clones })) })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    // One copy is stored and the loaded value itself is returned
+    cache.get(1).await.unwrap();
+    assert_eq!(clones.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    cache.get_with_expiry(2).await.unwrap();
+    assert_eq!(clones.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
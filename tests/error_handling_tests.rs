@@ -1,4 +1,9 @@
-use cache_rs::{Cache, Expiring};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use cache_rs::RetryPolicy;
+use cache_rs::{
+    Cache, CacheBuilder, CacheError, CircuitBreaker, Expiring, PoisonPolicy, ReloadErrorPolicy,
+    SharedError,
+};
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -215,3 +220,878 @@ async fn test_expiry_with_errors() {
     assert!(second_result.is_err());
     assert_eq!(call_count.load(std::sync::atomic::Ordering::SeqCst), 2);
 }
+
+#[tokio::test]
+async fn test_negative_caching() {
+    let call_count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let count_clone = call_count.clone();
+
+    let cache = CacheBuilder::new()
+        .loader(move |_key: i32| {
+            let counter = count_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Err::<Expiring<String>, _>(Box::new(CustomError {
+                    message: "Backend unavailable".to_string(),
+                })
+                    as Box<dyn std::error::Error + Send + Sync>)
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .negative_ttl(Duration::from_millis(100))
+        .build();
+
+    let first = cache.get(1).await.unwrap_err();
+    assert!(first.to_string().contains("Backend unavailable"));
+    assert_eq!(call_count.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    let second = cache.get(1).await.unwrap_err();
+    assert!(second.to_string().contains("Backend unavailable"));
+    assert_eq!(call_count.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    let CacheError::Load(second) = second else {
+        panic!("expected a load error, got {:?}", second);
+    };
+    let shared = second.downcast_ref::<SharedError>().unwrap();
+    assert!(shared.inner().downcast_ref::<CustomError>().is_some());
+    assert_eq!(cache.size(), 0);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert!(cache.get(1).await.is_err());
+    assert_eq!(call_count.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_negative_cache_cools_down_per_key() {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            calls_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                if key == 1 {
+                    return Err("backend down".into());
+                }
+                Ok(Expiring::never(key))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .negative_ttl(Duration::from_secs(60))
+        .build();
+
+    assert!(cache.get(1).await.is_err());
+    assert!(cache.get(1).await.is_err());
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    assert_eq!(cache.get(2).await.unwrap(), 2);
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_get_or_insert_with_respects_negative_cache() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .negative_ttl(Duration::from_secs(60))
+        .build();
+
+    let runs = std::cell::Cell::new(0);
+    let compute = || {
+        runs.set(runs.get() + 1);
+        "x".parse::<i32>()
+    };
+    assert!(
+        cache
+            .get_or_insert_with(1, Duration::from_secs(10), compute)
+            .await
+            .is_err()
+    );
+    assert!(
+        cache
+            .get_or_insert_with(1, Duration::from_secs(10), compute)
+            .await
+            .is_err()
+    );
+    assert_eq!(runs.get(), 1);
+    assert!(cache.get(1).await.is_err());
+}
+
+#[tokio::test]
+async fn test_failure_cooldown_fails_fast_until_it_passes() {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            calls_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                if key == 1 {
+                    return Err("backend down".into());
+                }
+                Ok(Expiring::never(key))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .failure_cooldown(Duration::from_millis(100))
+        .build();
+
+    // The failing call gets the loader's error, the next ones the cooldown
+    assert!(matches!(cache.get(1).await, Err(CacheError::Load(_))));
+    for _ in 0..3 {
+        match cache.get(1).await {
+            Err(CacheError::InCooldown { retry_after }) => {
+                assert!(!retry_after.is_zero() && retry_after <= Duration::from_millis(100));
+            }
+            other => panic!("expected a cooldown, got {:?}", other),
+        }
+    }
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // Other keys aren't held back
+    assert_eq!(cache.get(2).await.unwrap(), 2);
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(matches!(cache.get(1).await, Err(CacheError::Load(_))));
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_opens_and_recovers() {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let failing = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let (calls_clone, failing_clone) = (calls.clone(), failing.clone());
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            calls_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let failing = failing_clone.load(std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                if failing {
+                    return Err("backend down".into());
+                }
+                Ok(Expiring::never(key))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .circuit_breaker(CircuitBreaker::new(
+            3,
+            Duration::from_secs(10),
+            Duration::from_millis(100),
+        ))
+        .build();
+
+    for key in 0..3 {
+        assert!(matches!(cache.get(key).await, Err(CacheError::Load(_))));
+    }
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    // Open: every key fails fast, even once the backend is back
+    failing.store(false, std::sync::atomic::Ordering::SeqCst);
+    assert!(matches!(cache.get(3).await, Err(CacheError::CircuitOpen)));
+    assert!(matches!(cache.get(4).await, Err(CacheError::CircuitOpen)));
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    // After the cooldown a probe goes through and closes the circuit
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(cache.get(3).await.unwrap(), 3);
+    assert_eq!(cache.get(4).await.unwrap(), 4);
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_guards_batch_and_custom_loaders() {
+    let batches = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let batches_clone = batches.clone();
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .circuit_breaker(CircuitBreaker::new(
+            2,
+            Duration::from_secs(10),
+            Duration::from_millis(100),
+        ))
+        .build()
+        .with_batch_loader(move |keys: Vec<i32>| {
+            batches_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                Ok(keys
+                    .into_iter()
+                    .map(|key| (key, Expiring::never(key * 10)))
+                    .collect())
+            })
+        });
+
+    // Failures of a custom loader count towards opening the circuit
+    for key in 0..2 {
+        let result = cache
+            .get_or_load_with(key, |_| async { Err::<Expiring<i32>, _>("backend down") })
+            .await;
+        assert!(matches!(result, Err(CacheError::Load(_))));
+    }
+    let result = cache
+        .get_or_load_with(2, |key| async move {
+            Ok::<_, std::io::Error>(Expiring::never(key))
+        })
+        .await;
+    assert!(matches!(result, Err(CacheError::CircuitOpen)));
+
+    // An open circuit keeps the batch loader from being called
+    let results = cache.get_batch(vec![3, 4]).await;
+    assert!(
+        results
+            .iter()
+            .all(|result| matches!(result, Err(CacheError::CircuitOpen)))
+    );
+    assert_eq!(batches.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    // A successful batch probe closes it again
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let results = cache.get_batch(vec![3, 4]).await;
+    assert_eq!(
+        results.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+        vec![30, 40]
+    );
+    assert_eq!(cache.get(5).await.unwrap(), 5);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_load_timeout() {
+    let cache = CacheBuilder::new()
+        .loader(|key: u64| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(key)).await;
+                Ok(Expiring::with_duration(
+                    format!("loaded_{}", key),
+                    Duration::from_secs(10),
+                ))
+            })
+        })
+        .key_mapper(|key: &u64| key.to_string())
+        .load_timeout(Duration::from_millis(50))
+        .build();
+
+    let error = cache.get(500).await.unwrap_err();
+    assert!(matches!(
+        error,
+        CacheError::LoadTimeout(limit) if limit == Duration::from_millis(50)
+    ));
+    assert_eq!(cache.size(), 0);
+
+    let result = cache.get(1).await.unwrap();
+    assert_eq!(result, "loaded_1");
+    assert_eq!(cache.size(), 1);
+}
+
+fn flaky_loader(
+    attempts: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    failures: usize,
+) -> impl Fn(i32) -> cache_rs::LoadFuture<String> {
+    move |key: i32| {
+        let attempts = attempts.clone();
+        Box::pin(async move {
+            let attempt = attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if attempt < failures {
+                Err(Box::new(CustomError {
+                    message: format!("attempt {} failed", attempt + 1),
+                })
+                    as Box<dyn std::error::Error + Send + Sync>)
+            } else {
+                Ok(Expiring::with_duration(
+                    format!("loaded_{}", key),
+                    Duration::from_secs(10),
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_retry_until_success() {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let cache = CacheBuilder::new()
+        .loader(flaky_loader(attempts.clone(), 2))
+        .key_mapper(|key: &i32| key.to_string())
+        .retry_policy(RetryPolicy::new(3, Duration::from_millis(10)).exponential())
+        .build();
+
+    let result = cache.get(1).await.unwrap();
+    assert_eq!(result, "loaded_1");
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_retry_returns_last_error() {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let cache = CacheBuilder::new()
+        .loader(flaky_loader(attempts.clone(), 5))
+        .key_mapper(|key: &i32| key.to_string())
+        .with_retry(3, Duration::from_millis(1))
+        .build();
+
+    let error = cache.get(1).await.unwrap_err();
+    assert!(error.to_string().contains("attempt 3 failed"));
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_retry_skips_non_retryable_errors() {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let cache = CacheBuilder::new()
+        .loader(flaky_loader(attempts.clone(), 5))
+        .key_mapper(|key: &i32| key.to_string())
+        .retry_policy(
+            RetryPolicy::new(3, Duration::from_millis(1))
+                .retry_if(|error| error.downcast_ref::<CustomError>().is_none()),
+        )
+        .build();
+
+    assert!(cache.get(1).await.is_err());
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_get_or_else_returns_loaded_value() {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = Cache::new(flaky_loader(attempts, 0), |key: &i32| key.to_string());
+
+    let result = cache
+        .get_or_else(1, |_| panic!("fallback called on success"))
+        .await;
+    assert_eq!(result, "loaded_1");
+    assert_eq!(cache.size(), 1);
+}
+
+#[tokio::test]
+async fn test_get_or_else_does_not_cache_fallback() {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = Cache::new(flaky_loader(attempts.clone(), 1), |key: &i32| {
+        key.to_string()
+    });
+
+    let result = cache
+        .get_or_else(1, |err| format!("fallback ({})", err))
+        .await;
+    assert_eq!(result, "fallback (CustomError: attempt 1 failed)");
+    assert_eq!(cache.size(), 0);
+
+    // The loader recovers, and the fallback was never stored in its place
+    let result = cache.get_or_else(1, |_| "fallback".to_string()).await;
+    assert_eq!(result, "loaded_1");
+    assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+/// Loader whose first call is slow and second call is fast, each tagged with
+/// the order it started in
+#[cfg(feature = "tokio")]
+fn overlapping_loader(
+    first_delay: Duration,
+    second_delay: Duration,
+) -> impl Fn(i32) -> cache_rs::LoadFuture<String> {
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    move |key: i32| {
+        let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        let (delay, ttl) = if call == 1 {
+            (first_delay, Duration::from_secs(10))
+        } else {
+            (second_delay, Duration::from_secs(20))
+        };
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            Ok(Expiring::with_duration(
+                format!("{}_load{}", key, call),
+                ttl,
+            ))
+        })
+    }
+}
+
+// The refresh runs in a spawned task, so its loader needs Tokio's timers
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_later_load_wins_regardless_of_completion_order() {
+    for (refresh_delay, load_delay) in [(100, 10), (10, 100)] {
+        let cache = CacheBuilder::new()
+            .loader(overlapping_loader(
+                Duration::from_millis(refresh_delay),
+                Duration::from_millis(load_delay),
+            ))
+            .key_mapper(|key: &i32| key.to_string())
+            .refresh_ahead(0.1)
+            .build();
+        cache.insert(
+            1,
+            Expiring::with_duration("seed".to_string(), Duration::from_millis(200)),
+        );
+
+        // Concurrent gets share one load, so the overlap comes from a background
+        // refresh racing a get that missed after the entry was expired
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(cache.get(1).await.unwrap(), "seed");
+        cache.expire_at(&1, std::time::SystemTime::now() - Duration::from_secs(1));
+
+        // The get's load started second and has the fresher data, so it's what
+        // stays cached whichever load finishes first
+        assert_eq!(cache.get(1).await.unwrap(), "1_load2");
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let item = cache.get_with_expiry(1).await.unwrap();
+        assert_eq!(item.value, "1_load2");
+        assert!(item.remaining_ttl() > Duration::from_secs(15));
+    }
+}
+
+#[tokio::test]
+async fn test_load_does_not_overwrite_newer_insert() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Expiring::with_duration(
+                    format!("loaded_{}", key),
+                    Duration::from_secs(10),
+                ))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (loaded, _) = tokio::join!(cache.get(1), async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        cache.insert(
+            1,
+            Expiring::with_duration("inserted".to_string(), Duration::from_secs(1)),
+        );
+    });
+
+    assert_eq!(loaded.unwrap(), "inserted");
+    assert_eq!(cache.get(1).await.unwrap(), "inserted");
+}
+
+#[tokio::test]
+async fn test_waiters_take_over_when_leader_is_cancelled() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Expiring::never(key * 2))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (leader, waiter) = tokio::join!(
+        tokio::time::timeout(Duration::from_millis(20), cache.get(1)),
+        async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            cache.get(1).await
+        }
+    );
+
+    // The waiter doesn't hang on the abandoned load; it runs its own
+    assert!(leader.is_err());
+    assert_eq!(waiter.unwrap(), 2);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_new_leader_rechecks_before_loading() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Expiring::never(key * 2))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (leader, waiter, _) = tokio::join!(
+        tokio::time::timeout(Duration::from_millis(20), cache.get(1)),
+        async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            cache.get(1).await
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cache.insert(1, Expiring::never(100));
+        }
+    );
+
+    // The waiter missed before the insert, but takes over after it and finds the value
+    assert!(leader.is_err());
+    assert_eq!(waiter.unwrap(), 100);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_loader_panic_becomes_error() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                if key == 1 {
+                    panic!("backend returned garbage");
+                }
+                Ok(Expiring::never(key))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let error = cache.get(1).await.unwrap_err();
+    match error {
+        CacheError::LoaderPanicked(message) => assert_eq!(message, "backend returned garbage"),
+        other => panic!("expected LoaderPanicked, got {:?}", other),
+    }
+
+    // The cache keeps working for other keys and for retries of the same key
+    assert_eq!(cache.get(2).await.unwrap(), 2);
+    assert!(cache.get(1).await.is_err());
+    assert_eq!(cache.size(), 1);
+}
+
+#[tokio::test]
+async fn test_stale_fallback_on_reload_error() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let load = loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                if load > 1 {
+                    return Err("backend down".into());
+                }
+                Ok(Expiring::with_duration(
+                    format!("good_{}", key),
+                    Duration::from_millis(50),
+                ))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .on_reload_error(ReloadErrorPolicy::StaleFallback {
+            grace: Duration::from_secs(10),
+        })
+        .build();
+
+    assert_eq!(cache.get(1).await.unwrap(), "good_1");
+    tokio::time::sleep(Duration::from_millis(80)).await;
+
+    // The reload fails, so the last good value is served and kept for the grace period
+    assert_eq!(cache.get(1).await.unwrap(), "good_1");
+    assert!(cache.ttl_remaining(&1).unwrap() > Duration::from_secs(5));
+    assert_eq!(cache.get(1).await.unwrap(), "good_1");
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    // Without a stale value the error still comes through
+    assert_eq!(cache.get(2).await.unwrap_err().to_string(), "backend down");
+}
+
+#[tokio::test]
+async fn test_loader_error_is_load_variant() {
+    let cache = Cache::new(
+        |_key: i32| {
+            Box::pin(async move {
+                Err::<Expiring<String>, _>(Box::new(CustomError {
+                    message: "not found".to_string(),
+                })
+                    as Box<dyn std::error::Error + Send + Sync>)
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    match cache.get(1).await.unwrap_err() {
+        CacheError::Load(error) => {
+            assert_eq!(
+                error.downcast_ref::<CustomError>().unwrap().message,
+                "not found"
+            )
+        }
+        other => panic!("expected Load, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_poisoned_shard_is_reported() {
+    let cache = Cache::with_shards(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+        1,
+    );
+    cache.get(1).await.unwrap();
+
+    // The update closure runs under the shard lock, so its panic poisons it
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cache.update(&1, |_| panic!("bad update"));
+    }));
+    assert!(panicked.is_err());
+
+    // By default the shard reads as empty, so keys load but aren't cached
+    assert_eq!(cache.get(2).await.unwrap(), 2);
+    assert_eq!(cache.size(), 0);
+}
+
+fn poisoned_cache(
+    policy: PoisonPolicy,
+    loads: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) -> Cache<i32, i32, impl Fn(i32) -> cache_rs::LoadFuture<i32>, impl Fn(&i32) -> String> {
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::never(key)) })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .shards(1)
+        .poison_policy(policy)
+        .build();
+    cache.insert(1, Expiring::never(1));
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cache.update(&1, |_| panic!("bad update"));
+    }));
+    assert!(panicked.is_err());
+    cache
+}
+
+#[tokio::test]
+async fn test_poison_policy_ignore_degrades_silently() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = poisoned_cache(PoisonPolicy::Ignore, loads.clone());
+
+    assert_eq!(cache.size(), 0);
+    cache.delete(1);
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_poison_policy_error_surfaces_lock_poisoned() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = poisoned_cache(PoisonPolicy::Error, loads.clone());
+
+    assert!(matches!(
+        cache.get(1).await.unwrap_err(),
+        CacheError::LockPoisoned
+    ));
+    assert!(matches!(
+        cache.get_cached_only(1).await.unwrap_err(),
+        CacheError::LockPoisoned
+    ));
+    assert!(matches!(
+        cache.get_many(vec![1, 2]).await[1],
+        Err(CacheError::LockPoisoned)
+    ));
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    // Methods without an error to return degrade like Ignore instead of panicking
+    assert_eq!(cache.size(), 0);
+    cache.delete(1);
+    cache.insert(2, Expiring::never(2));
+    assert_eq!(cache.try_peek(&2), None);
+}
+
+#[tokio::test]
+async fn test_poison_policy_recover_keeps_the_data() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let cache = poisoned_cache(PoisonPolicy::Recover, loads.clone());
+
+    assert_eq!(cache.size(), 1);
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 0);
+    assert_eq!(cache.get(2).await.unwrap(), 2);
+    cache.delete(1);
+    assert_eq!(cache.size(), 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_impatient_waiter_gives_up_without_cancelling_load() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(150)).await;
+                Ok(Expiring::never(key))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (leader, impatient, patient) = tokio::join!(
+        cache.get(1),
+        async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cache.get_timeout(1, Duration::from_millis(30)).await
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cache.get_timeout(1, Duration::from_secs(5)).await
+        },
+    );
+
+    assert!(matches!(impatient, Err(CacheError::WaitTimeout(_))));
+    assert_eq!(leader.unwrap(), 1);
+    assert_eq!(patient.unwrap(), 1);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_rejected_key_never_loads() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::with_key_validation(
+        move |key: String| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::never(key.len())) })
+        },
+        |key: &String| {
+            if key.is_empty() {
+                return Err("empty keys aren't allowed");
+            }
+            Ok(key.clone())
+        },
+    );
+
+    let result = cache.get(String::new()).await;
+    match result {
+        Err(CacheError::InvalidKey(error)) => {
+            assert_eq!(error.to_string(), "empty keys aren't allowed")
+        }
+        other => panic!("expected an invalid key error, got {:?}", other),
+    }
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    // Infallible methods ignore the rejected key
+    cache.insert(String::new(), Expiring::never(0));
+    assert_eq!(cache.size(), 0);
+    assert_eq!(cache.try_peek(&String::new()), None);
+
+    let results = cache
+        .get_many(vec!["abc".to_string(), String::new(), "abc".to_string()])
+        .await;
+    assert_eq!(results[0].as_ref().unwrap(), &3);
+    assert!(matches!(results[1], Err(CacheError::InvalidKey(_))));
+    assert_eq!(results[2].as_ref().unwrap(), &3);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_builder_try_key_mapper() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key * 2)) }))
+        .try_key_mapper(|key: &i32| {
+            if *key < 0 {
+                return Err(format!("negative key {}", key));
+            }
+            Ok(key.to_string())
+        })
+        .build();
+
+    assert_eq!(cache.get(4).await.unwrap(), 8);
+    let error = cache.get(-1).await.unwrap_err();
+    assert_eq!(error.to_string(), "invalid key: negative key -1");
+}
+
+#[tokio::test]
+async fn test_colliding_keys_are_detected() {
+    let cache = Cache::new(
+        |key: (String, String)| {
+            Box::pin(async move { Ok(Expiring::never(format!("{}+{}", key.0, key.1))) })
+        },
+        // Loses the boundary between the parts when they contain ':'
+        |key: &(String, String)| format!("{}:{}", key.0, key.1),
+    )
+    .detect_key_collisions();
+
+    let first = ("a:b".to_string(), "c".to_string());
+    let second = ("a".to_string(), "b:c".to_string());
+    assert_eq!(cache.get(first.clone()).await.unwrap(), "a:b+c");
+
+    match cache.get(second.clone()).await {
+        Err(CacheError::KeyCollision(identifier)) => assert_eq!(identifier, "a:b:c"),
+        other => panic!("expected a key collision, got {:?}", other),
+    }
+    cache.insert(second, Expiring::never("overwritten".to_string()));
+    assert_eq!(cache.get(first).await.unwrap(), "a:b+c");
+}
+
+#[tokio::test]
+async fn test_get_all_names_the_failing_key() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                if key == 3 {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    return Err("backend rejected key 3".into());
+                }
+                Ok(Expiring::never(key * 10))
+            })
+        },
+        |key: &i32| format!("item:{}", key),
+    );
+
+    assert_eq!(
+        cache.get_all(vec![1, 2, 1]).await.unwrap(),
+        vec![10, 20, 10]
+    );
+
+    match cache.get_all(vec![4, 3, 5]).await {
+        Err(CacheError::BatchFailed { identifier, source }) => {
+            assert_eq!(identifier, "item:3");
+            assert!(matches!(*source, CacheError::Load(_)));
+            assert_eq!(source.to_string(), "backend rejected key 3");
+        }
+        other => panic!("expected a batch failure, got {:?}", other),
+    }
+    // Keys that loaded before the failure stay cached
+    assert_eq!(cache.get_cached_only(4).await.unwrap(), 40);
+    assert_eq!(cache.get_cached_only(5).await.unwrap(), 50);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_get_by_deadline_bounds_the_load() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(Expiring::never(key))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let past = std::time::Instant::now() - Duration::from_millis(1);
+    assert!(matches!(
+        cache.get_by_deadline(1, past).await,
+        Err(CacheError::DeadlineExceeded)
+    ));
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    let too_soon = std::time::Instant::now() + Duration::from_millis(30);
+    assert!(matches!(
+        cache.get_by_deadline(1, too_soon).await,
+        Err(CacheError::DeadlineExceeded)
+    ));
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert_eq!(cache.size(), 0);
+
+    let later = std::time::Instant::now() + Duration::from_secs(5);
+    assert_eq!(cache.get_by_deadline(1, later).await.unwrap(), 1);
+}
@@ -0,0 +1,227 @@
+use cache_rs::{Cache, CacheBuilder, CacheEvent, EvictionReason, Expiring, LoadFuture};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type EvictionLog = Arc<Mutex<Vec<(String, String, EvictionReason)>>>;
+
+fn recording_cache(
+    ttl: Duration,
+    capacity: usize,
+    log: &EvictionLog,
+) -> Cache<i32, String, impl Fn(i32) -> LoadFuture<String>, impl Fn(&i32) -> String> {
+    let log_clone = log.clone();
+
+    CacheBuilder::new()
+        .loader(move |key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, ttl))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .max_capacity(capacity)
+        .on_evict(move |identifier: &str, value: &String, reason| {
+            log_clone
+                .lock()
+                .unwrap()
+                .push((identifier.to_string(), value.clone(), reason));
+        })
+        .build()
+}
+
+#[tokio::test]
+async fn test_on_evict_manual() {
+    let log = EvictionLog::default();
+    let cache = recording_cache(Duration::from_secs(10), 10, &log);
+
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    cache.delete(1);
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![(
+            "1".to_string(),
+            "loaded_1".to_string(),
+            EvictionReason::Manual
+        )]
+    );
+
+    cache.delete_all();
+    assert_eq!(log.lock().unwrap().len(), 2);
+    assert_eq!(log.lock().unwrap()[1].2, EvictionReason::Manual);
+
+    // Deleting an absent key doesn't fire the callback
+    cache.delete(3);
+    assert_eq!(log.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_on_evict_expired() {
+    let log = EvictionLog::default();
+    let cache = recording_cache(Duration::from_millis(50), 10, &log);
+
+    cache.get(1).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(cache.purge_expired(), 1);
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![(
+            "1".to_string(),
+            "loaded_1".to_string(),
+            EvictionReason::Expired
+        )]
+    );
+
+    // Reloading over an expired entry also reports it as expired
+    cache.get(2).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cache.get(2).await.unwrap();
+    assert_eq!(log.lock().unwrap()[1].2, EvictionReason::Expired);
+}
+
+#[tokio::test]
+async fn test_on_evict_capacity() {
+    let log = EvictionLog::default();
+    let cache = recording_cache(Duration::from_secs(10), 2, &log);
+
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    cache.get(3).await.unwrap();
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![(
+            "1".to_string(),
+            "loaded_1".to_string(),
+            EvictionReason::Capacity
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_eviction_batch_evicts_in_chunks() {
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let evicted_clone = evicted.clone();
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .max_capacity(10)
+        .eviction_batch(4)
+        .on_evict(move |identifier: &str, _: &i32, _| {
+            evicted_clone.lock().unwrap().push(identifier.to_string());
+        })
+        .build();
+
+    for key in 0..10 {
+        cache.get(key).await.unwrap();
+    }
+    assert!(evicted.lock().unwrap().is_empty());
+
+    // Going over evicts a whole batch, oldest first
+    cache.get(10).await.unwrap();
+    assert_eq!(*evicted.lock().unwrap(), vec!["0", "1", "2", "3"]);
+    assert_eq!(cache.size(), 7);
+
+    // The next inserts fit in the room that was made
+    for key in 11..14 {
+        cache.get(key).await.unwrap();
+    }
+    assert_eq!(evicted.lock().unwrap().len(), 4);
+    assert_eq!(cache.size(), 10);
+
+    cache.get(14).await.unwrap();
+    assert_eq!(evicted.lock().unwrap().len(), 8);
+    assert_eq!(cache.size(), 7);
+}
+
+#[tokio::test]
+async fn test_on_evict_replaced() {
+    let log = EvictionLog::default();
+    let cache = recording_cache(Duration::from_secs(10), 10, &log);
+
+    cache.get(1).await.unwrap();
+    cache.insert(
+        1,
+        Expiring::with_duration("manual".to_string(), Duration::from_secs(10)),
+    );
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![(
+            "1".to_string(),
+            "loaded_1".to_string(),
+            EvictionReason::Replaced
+        )]
+    );
+    assert_eq!(cache.get(1).await.unwrap(), "manual");
+}
+
+#[tokio::test]
+async fn test_subscribe_receives_events() {
+    let log = EvictionLog::default();
+    let cache = recording_cache(Duration::from_millis(50), 10, &log);
+    let mut events = cache.subscribe();
+
+    cache.get(1).await.unwrap();
+    cache.delete(1);
+    cache.get(2).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cache.purge_expired();
+
+    assert_eq!(
+        events.recv().await.unwrap(),
+        CacheEvent::Inserted("1".to_string())
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        CacheEvent::Evicted("1".to_string())
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        CacheEvent::Inserted("2".to_string())
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        CacheEvent::Expired("2".to_string())
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_shrink_to_evicts_expired_then_lru() {
+    let log = EvictionLog::default();
+    let cache = recording_cache(Duration::from_secs(10), 10, &log);
+
+    for key in 1..=6 {
+        cache.get(key).await.unwrap();
+    }
+    cache.insert(
+        7,
+        Expiring::with_duration("short".to_string(), Duration::from_millis(10)),
+    );
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    cache.get(1).await.unwrap();
+
+    assert_eq!(cache.shrink_to(4), 3);
+    assert_eq!(cache.size(), 4);
+    let evicted: Vec<_> = log
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(identifier, _, reason)| (identifier.clone(), *reason))
+        .collect();
+    assert_eq!(
+        evicted,
+        vec![
+            ("7".to_string(), EvictionReason::Expired),
+            ("2".to_string(), EvictionReason::Capacity),
+            ("3".to_string(), EvictionReason::Capacity),
+        ]
+    );
+
+    // Already small enough: nothing to do
+    assert_eq!(cache.shrink_to(4), 0);
+    assert_eq!(cache.shrink_to(0), 4);
+    assert_eq!(cache.size(), 0);
+}
@@ -0,0 +1,186 @@
+use cache_rs::{ByExpiry, Expiring};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn test_remaining_ttl_fresh_entry() {
+    let item = Expiring::with_duration("value", Duration::from_secs(60));
+
+    let remaining = item.remaining_ttl();
+    assert!(remaining <= Duration::from_secs(60));
+    assert!(remaining > Duration::from_secs(59));
+
+    let ttl = item.time_to_live().unwrap();
+    assert!(ttl <= Duration::from_secs(60));
+    assert!(ttl > Duration::from_secs(59));
+}
+
+#[test]
+fn test_remaining_ttl_nearly_expired_entry() {
+    let item = Expiring::with_duration("value", Duration::from_millis(200));
+
+    let remaining = item.remaining_ttl();
+    assert!(remaining > Duration::ZERO);
+    assert!(remaining <= Duration::from_millis(200));
+    assert!(item.time_to_live().is_some());
+}
+
+#[test]
+fn test_remaining_ttl_expired_entry() {
+    let item = Expiring::new("value", SystemTime::now() - Duration::from_secs(1));
+
+    assert!(item.is_expired());
+    assert_eq!(item.remaining_ttl(), Duration::ZERO);
+    assert_eq!(item.time_to_live(), None);
+}
+
+#[test]
+fn test_never_expiring_entry() {
+    let item = Expiring::never("value");
+
+    assert_eq!(item.expires_at, None);
+    assert!(!item.is_expired());
+    assert_eq!(item.remaining_ttl(), Duration::MAX);
+    assert_eq!(item.time_to_live(), Some(Duration::MAX));
+}
+
+#[derive(Debug, Clone)]
+struct User {
+    name: String,
+    age: u32,
+}
+
+#[test]
+fn test_map_preserves_expiry() {
+    let user = User {
+        name: "alice".to_string(),
+        age: 30,
+    };
+    let item = Expiring::with_duration(user, Duration::from_secs(60));
+    let expires_at = item.expires_at;
+
+    let name = item.map(|user| user.name);
+    assert_eq!(name.value, "alice");
+    assert_eq!(name.expires_at, expires_at);
+
+    let never = Expiring::never(5).map(|n| n * 2);
+    assert_eq!(never.value, 10);
+    assert_eq!(never.expires_at, None);
+}
+
+#[test]
+fn test_as_ref_borrows_value() {
+    let item = Expiring::with_duration(
+        User {
+            name: "bob".to_string(),
+            age: 42,
+        },
+        Duration::from_secs(60),
+    );
+
+    let age = item.as_ref().map(|user| user.age);
+    assert_eq!(age.value, 42);
+    assert_eq!(age.expires_at, item.expires_at);
+    assert_eq!(item.value.name, "bob");
+}
+
+#[tokio::test]
+async fn test_ttl_remaining_through_cache() {
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = cache_rs::Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_secs(10))) })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    assert_eq!(cache.ttl_remaining(&1), None);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    cache.get(1).await.unwrap();
+    let remaining = cache.ttl_remaining(&1).unwrap();
+    assert!(remaining <= Duration::from_secs(10));
+    assert!(remaining > Duration::from_secs(9));
+
+    cache.insert(2, Expiring::never(2));
+    assert_eq!(cache.ttl_remaining(&2), Some(Duration::MAX));
+
+    cache.insert(
+        3,
+        Expiring::new(3, SystemTime::now() - Duration::from_secs(1)),
+    );
+    assert_eq!(cache.ttl_remaining(&3), None);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_by_expiry_heap_pops_soonest_first() {
+    let now = SystemTime::now();
+    let items = [
+        Expiring::new("in_30s", now + Duration::from_secs(30)),
+        Expiring::never("forever"),
+        Expiring::new("in_10s", now + Duration::from_secs(10)),
+        Expiring::new("expired", now - Duration::from_secs(1)),
+        Expiring::new("in_20s", now + Duration::from_secs(20)),
+    ];
+
+    let mut heap: BinaryHeap<_> = items
+        .into_iter()
+        .map(|item| Reverse(ByExpiry(item)))
+        .collect();
+    let order: Vec<_> = std::iter::from_fn(|| heap.pop())
+        .map(|Reverse(item)| item.into_inner().value)
+        .collect();
+    assert_eq!(
+        order,
+        vec!["expired", "in_10s", "in_20s", "in_30s", "forever"]
+    );
+}
+
+#[test]
+fn test_is_expired_at_boundaries() {
+    let expires_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let item = Expiring::new("value", expires_at);
+    let nanosecond = Duration::from_nanos(1);
+
+    assert!(!item.is_expired_at(expires_at - nanosecond));
+    // Still live at the exact expiry instant
+    assert!(!item.is_expired_at(expires_at));
+    assert!(item.is_expired_at(expires_at + nanosecond));
+
+    assert!(!Expiring::never("value").is_expired_at(expires_at + Duration::from_secs(1 << 40)));
+}
+
+#[test]
+fn test_is_stale_at_boundaries() {
+    let item = Expiring::with_soft_ttl("value", Duration::from_secs(10), Duration::from_secs(60));
+    let soft_expires_at = item.soft_expires_at().unwrap();
+
+    assert!(!item.is_stale_at(soft_expires_at));
+    assert!(item.is_stale_at(soft_expires_at + Duration::from_nanos(1)));
+    assert!(!item.is_expired_at(soft_expires_at + Duration::from_nanos(1)));
+}
+
+#[test]
+fn test_with_soft_expiry_keeps_soft_expiry_within_hard() {
+    let now = SystemTime::now();
+    let item = Expiring::with_soft_expiry(
+        "value",
+        now + Duration::from_secs(10),
+        Some(now + Duration::from_secs(60)),
+    );
+    assert_eq!(item.soft_expires_at(), Some(now + Duration::from_secs(10)));
+    assert_eq!(item.expires_at, Some(now + Duration::from_secs(60)));
+
+    let clamped =
+        Expiring::with_soft_expiry("value", now + Duration::from_secs(90), item.expires_at);
+    assert_eq!(clamped.soft_expires_at(), item.expires_at);
+
+    let never = Expiring::with_soft_expiry("value", now, None);
+    assert!(never.is_stale_at(now + Duration::from_secs(1)));
+    assert!(!never.is_expired_at(now + Duration::from_secs(1)));
+    assert_eq!(Expiring::never("value").soft_expires_at(), None);
+}
@@ -0,0 +1,434 @@
+use cache_rs::{Cache, CacheBuilder, CacheOutcome, CacheStats, Expiring};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_snapshot_excludes_expired() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                let ttl = if key == 0 {
+                    Duration::from_millis(50)
+                } else {
+                    Duration::from_secs(10)
+                };
+                Ok(Expiring::with_duration(value, ttl))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    for key in 0..4 {
+        cache.get(key).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut snapshot = cache.snapshot();
+    snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let identifiers: Vec<&str> = snapshot.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(identifiers, vec!["1", "2", "3"]);
+    assert_eq!(snapshot[0].1.value, "loaded_1");
+    assert_eq!(cache.size(), 4);
+}
+
+#[tokio::test]
+async fn test_active_size_excludes_expired() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                let ttl = if key < 2 {
+                    Duration::from_millis(50)
+                } else {
+                    Duration::from_secs(10)
+                };
+                Ok(Expiring::with_duration(key, ttl))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    for key in 0..3 {
+        cache.get(key).await.unwrap();
+    }
+    assert_eq!(cache.active_size(), 3);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(cache.size(), 3);
+    assert_eq!(cache.active_size(), 1);
+}
+
+#[tokio::test]
+async fn test_keys_and_values_list_live_entries() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                let ttl = if key == 2 {
+                    Duration::from_millis(50)
+                } else {
+                    Duration::from_secs(10)
+                };
+                Ok(Expiring::with_duration(key * 10, ttl))
+            })
+        },
+        |key: &i32| format!("key{}", key),
+    );
+
+    for key in 1..=3 {
+        cache.get(key).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut keys = cache.keys();
+    keys.sort();
+    assert_eq!(keys, vec!["key1", "key3"]);
+
+    let mut values = cache.values();
+    values.sort();
+    assert_eq!(values, vec![10, 30]);
+}
+
+#[tokio::test]
+async fn test_expiring_within_lists_soon_to_expire_entries() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| format!("key{}", key),
+    );
+    cache.insert(1, Expiring::with_duration(1, Duration::from_secs(3600)));
+    cache.insert(2, Expiring::with_duration(2, Duration::from_secs(20)));
+    cache.insert(3, Expiring::with_duration(3, Duration::from_secs(5)));
+    cache.insert(4, Expiring::never(4));
+    cache.insert(
+        5,
+        Expiring::new(5, std::time::SystemTime::now() - Duration::from_secs(1)),
+    );
+
+    assert_eq!(
+        cache.expiring_within(Duration::from_secs(60)),
+        vec!["key3", "key2"]
+    );
+    assert_eq!(cache.expiring_within(Duration::from_secs(10)), vec!["key3"]);
+    assert!(cache.expiring_within(Duration::from_secs(1)).is_empty());
+}
+
+#[tokio::test]
+async fn test_get_instrumented_reports_hit_or_miss() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(Expiring::with_duration(key * 2, Duration::from_secs(10)))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    let (value, outcome) = cache.get_instrumented(21).await.unwrap();
+    assert_eq!(value, 42);
+    match outcome {
+        CacheOutcome::Miss { load_duration } => {
+            assert!(load_duration >= Duration::from_millis(20));
+        }
+        CacheOutcome::Hit => panic!("first lookup should miss"),
+    }
+
+    let (value, outcome) = cache.get_instrumented(21).await.unwrap();
+    assert_eq!(value, 42);
+    assert_eq!(outcome, CacheOutcome::Hit);
+}
+
+#[tokio::test]
+async fn test_debug_output() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(format!("loaded_{}", key))) }),
+        |key: &i32| key.to_string(),
+    );
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+
+    let summary = format!("{:?}", cache);
+    assert!(summary.starts_with("Cache {"));
+    assert!(summary.contains("size: 2"));
+    assert!(summary.contains("<loader>"));
+    assert!(!summary.contains("loaded_1"));
+
+    let entries = format!("{:?}", cache.debug_entries());
+    assert!(entries.contains("\"1\""));
+    assert!(entries.contains("loaded_1"));
+}
+
+#[tokio::test]
+async fn test_concurrent_gets_are_coalesced() {
+    const CALLERS: u64 = 8;
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Arc::new(Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Expiring::never(key * 2))
+            })
+        },
+        |key: &i32| key.to_string(),
+    ));
+
+    let handles: Vec<_> = (0..CALLERS)
+        .map(|_| {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.get(1).await.unwrap() })
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.await.unwrap(), 2);
+    }
+
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            hits: 0,
+            misses: CALLERS,
+            coalesced_loads: CALLERS - 1,
+            near_miss_reloads: 0,
+        }
+    );
+
+    cache.get(1).await.unwrap();
+    assert_eq!(cache.stats().hits, 1);
+}
+
+#[tokio::test]
+async fn test_capacity_introspection() {
+    let unbounded = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    );
+    unbounded.get(1).await.unwrap();
+    assert_eq!(unbounded.capacity(), None);
+    assert_eq!(unbounded.remaining_capacity(), None);
+
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(key)) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .max_capacity(5)
+        .build();
+    assert_eq!(cache.capacity(), Some(5));
+    assert_eq!(cache.remaining_capacity(), Some(5));
+
+    for key in 0..3 {
+        cache.get(key).await.unwrap();
+    }
+    assert_eq!(cache.remaining_capacity(), Some(2));
+
+    // Filling past the limit evicts, so size never exceeds capacity
+    for key in 3..10 {
+        cache.get(key).await.unwrap();
+        assert!(cache.size() <= cache.capacity().unwrap());
+    }
+    assert_eq!(cache.size(), 5);
+    assert_eq!(cache.remaining_capacity(), Some(0));
+}
+
+#[tokio::test]
+async fn test_load_latency_percentiles() {
+    let cache = Cache::new(
+        |delay_ms: u64| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                Ok(Expiring::never(delay_ms))
+            })
+        },
+        |delay_ms: &u64| delay_ms.to_string(),
+    );
+    assert_eq!(cache.load_latency_percentile(0.5), None);
+
+    // Nineteen fast loads and one slow one
+    for _ in 0..19 {
+        cache.delete(5);
+        cache.get(5).await.unwrap();
+    }
+    cache.get(50).await.unwrap();
+
+    let p50 = cache.load_latency_percentile(0.5).unwrap();
+    let p99 = cache.load_latency_percentile(0.99).unwrap();
+    assert!(p50 >= Duration::from_millis(5) && p50 < Duration::from_millis(20));
+    assert!(p99 >= Duration::from_millis(50) && p99 < Duration::from_millis(100));
+
+    // Hits don't call the loader, so they don't move the percentiles
+    cache.get(50).await.unwrap();
+    assert_eq!(cache.load_latency_percentile(0.99), Some(p99));
+}
+
+#[tokio::test]
+async fn test_near_miss_reloads() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_millis(50))) })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    cache.get(1).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cache.get_with_expiry(1).await.unwrap();
+    assert_eq!(cache.stats().near_miss_reloads, 1);
+
+    // A first load or one long after expiry isn't a near miss
+    cache.insert(
+        2,
+        Expiring::new(2, std::time::SystemTime::now() - Duration::from_secs(60)),
+    );
+    cache.get(2).await.unwrap();
+    cache.get(3).await.unwrap();
+    assert_eq!(cache.stats().near_miss_reloads, 1);
+}
+
+#[tokio::test]
+async fn test_range_and_remove_prefix() {
+    for ordered in [false, true] {
+        let builder = CacheBuilder::new()
+            .loader(|key: String| Box::pin(async move { Ok(Expiring::never(key.len())) }))
+            .key_mapper(|key: &String| key.clone());
+        let cache = if ordered {
+            builder.ordered_index().build()
+        } else {
+            builder.build()
+        };
+        for identifier in [
+            "user:1:profile",
+            "user:1:settings",
+            "user:2:profile",
+            "user:10:profile",
+            "order:7",
+        ] {
+            cache.insert(identifier.to_string(), Expiring::never(identifier.len()));
+        }
+
+        let users: Vec<String> = cache
+            .range("user:".to_string().."user;".to_string())
+            .into_iter()
+            .map(|(identifier, _)| identifier)
+            .collect();
+        assert_eq!(
+            users,
+            vec![
+                "user:10:profile",
+                "user:1:profile",
+                "user:1:settings",
+                "user:2:profile"
+            ]
+        );
+        assert_eq!(
+            cache.range(.."p".to_string()),
+            vec![("order:7".to_string(), 7)]
+        );
+
+        assert_eq!(cache.remove_prefix("user:1:"), 2);
+        assert_eq!(cache.size(), 3);
+        assert!(cache.try_peek(&"user:10:profile".to_string()).is_some());
+        assert_eq!(cache.range(..).len(), 3);
+    }
+}
+
+#[tokio::test]
+async fn test_keys_typed_enumerates_remembered_keys() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key * 10)) }),
+        |key: &i32| format!("key{}", key),
+    );
+    cache.get(1).await.unwrap();
+    assert!(cache.keys_typed().is_empty());
+
+    // Keys are kept with entries, so one cached before has none until it's stored again
+    let cache = cache.remember_keys();
+    for key in 1..=4 {
+        cache.get(key).await.unwrap();
+    }
+    let mut keys = cache.keys_typed();
+    keys.sort();
+    assert_eq!(keys, vec![2, 3, 4]);
+    cache.insert(1, Expiring::never(10));
+    let mut keys = cache.keys_typed();
+    keys.sort();
+    assert_eq!(keys, vec![1, 2, 3, 4]);
+
+    assert_eq!(cache.delete_where(|key| key % 2 == 0), 2);
+    let mut keys = cache.keys_typed();
+    keys.sort();
+    assert_eq!(keys, vec![1, 3]);
+    assert_eq!(cache.size(), 2);
+}
+
+#[tokio::test]
+async fn test_remembered_keys_live_and_die_with_entries() {
+    let release = Arc::new(tokio::sync::Notify::new());
+    let release_clone = release.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let release = release_clone.clone();
+            Box::pin(async move {
+                if key < 0 {
+                    return Err("no such key".into());
+                }
+                if key == 1 {
+                    release.notified().await;
+                }
+                Ok(Expiring::never(key * 10))
+            })
+        },
+        |key: &i32| format!("key{}", key),
+    )
+    .remember_keys();
+
+    // Enumerating while a load is running doesn't lose its key once it lands
+    let (loaded, keys) = tokio::join!(cache.get(1), async {
+        tokio::task::yield_now().await;
+        let keys = cache.keys_typed();
+        release.notify_one();
+        keys
+    });
+    assert_eq!(loaded.unwrap(), 10);
+    assert!(keys.is_empty());
+    assert_eq!(cache.keys_typed(), vec![1]);
+    release.notify_one();
+    assert!(
+        cache
+            .reload_all()
+            .await
+            .iter()
+            .all(|(_, result)| result.is_ok())
+    );
+
+    // Misses and lookups that store nothing keep no key
+    assert!(cache.get(-1).await.is_err());
+    assert!(cache.try_peek(&2).is_none());
+    assert_eq!(cache.keys_typed(), vec![1]);
+
+    cache.delete(1);
+    assert!(cache.keys_typed().is_empty());
+}
+
+#[tokio::test]
+async fn test_last_accessed_advances_on_reads() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| format!("key{}", key),
+    );
+    assert_eq!(cache.last_accessed(&1), None);
+
+    cache.get(1).await.unwrap();
+    let first = cache.last_accessed(&1).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(cache.last_accessed(&1), Some(first));
+
+    cache.get(1).await.unwrap();
+    let second = cache.last_accessed(&1).unwrap();
+    assert!(second > first);
+
+    let snapshot = cache.dump();
+    assert_eq!(snapshot.entries[0].last_accessed, Some(second));
+}
@@ -0,0 +1,81 @@
+use cache_rs::{Expiring, KeyedCache};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Deliberately has no `Display` or `ToString`, so it can't be turned into a string
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct UserId(u32);
+
+#[tokio::test]
+async fn test_keyed_integer_keys() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+
+    let cache = KeyedCache::new(move |key: i32| {
+        let counter = counter_clone.clone();
+        Box::pin(async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Expiring::with_duration(key * 2, Duration::from_secs(10)))
+        })
+    });
+
+    assert_eq!(cache.get(21).await.unwrap(), 42);
+    assert_eq!(cache.get(21).await.unwrap(), 42);
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.size(), 1);
+
+    cache.delete(&21);
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_keyed_skips_caching_expired_loads() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+    let cache = KeyedCache::new(move |key: i32| {
+        counter_clone.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(Expiring::with_duration(key, Duration::ZERO)) })
+    });
+
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_keyed_tuple_keys() {
+    let cache = KeyedCache::new(|key: (String, u32)| {
+        Box::pin(async move {
+            let (category, page) = key;
+            let result = format!("{}:page{}", category, page);
+            Ok(Expiring::with_duration(result, Duration::from_secs(1)))
+        })
+    });
+
+    let result = cache.get(("products".to_string(), 2)).await.unwrap();
+    assert_eq!(result, "products:page2");
+
+    cache.get(("products".to_string(), 3)).await.unwrap();
+    assert_eq!(cache.size(), 2);
+}
+
+#[tokio::test]
+async fn test_keyed_without_string_conversion() {
+    let cache = KeyedCache::new(|key: UserId| {
+        Box::pin(async move {
+            Ok(Expiring::with_duration(
+                format!("user_{}", key.0),
+                Duration::from_millis(50),
+            ))
+        })
+    });
+
+    assert_eq!(cache.get(UserId(7)).await.unwrap(), "user_7");
+    assert_eq!(cache.size(), 1);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(cache.purge_expired(), 1);
+    assert_eq!(cache.size(), 0);
+}
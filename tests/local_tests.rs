@@ -0,0 +1,47 @@
+use cache_rs::{Expiring, LocalCache};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+
+#[tokio::test]
+async fn test_local_cache_with_non_send_loader() {
+    let local = LocalSet::new();
+    local
+        .run_until(async {
+            let loads = Rc::new(Cell::new(0));
+            let loads_clone = loads.clone();
+            let cache = Rc::new(LocalCache::new(
+                move |key: u32| {
+                    // The future holds an `Rc`, so it isn't `Send`
+                    let loads = loads_clone.clone();
+                    Box::pin(async move {
+                        tokio::task::yield_now().await;
+                        loads.set(loads.get() + 1);
+                        Ok(Expiring::with_duration(
+                            Rc::new(format!("value_{}", key)),
+                            Duration::from_secs(60),
+                        ))
+                    })
+                },
+                |key: &u32| key.to_string(),
+            ));
+
+            let task_cache = cache.clone();
+            let first = tokio::task::spawn_local(async move { task_cache.get(1).await })
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(*first, "value_1");
+
+            let second = cache.get(1).await.unwrap();
+            assert!(Rc::ptr_eq(&first, &second));
+            assert_eq!(loads.get(), 1);
+
+            cache.delete(&1);
+            assert_eq!(cache.size(), 0);
+            cache.get(1).await.unwrap();
+            assert_eq!(loads.get(), 2);
+        })
+        .await;
+}
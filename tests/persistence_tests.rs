@@ -0,0 +1,307 @@
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use cache_rs::BackingFuture;
+use cache_rs::{
+    BackingStore, Cache, CacheBuilder, CacheSnapshot, Expiring, MemoryBackingStore, SnapshotEntry,
+    ValueSource,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+fn counting_cache(
+    counter: Arc<AtomicUsize>,
+) -> Cache<i32, String, impl Fn(i32) -> cache_rs::LoadFuture<String>, impl Fn(&i32) -> String> {
+    Cache::new(
+        move |key: i32| {
+            let counter = counter.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(60)))
+            })
+        },
+        |key: &i32| key.to_string(),
+    )
+}
+
+#[tokio::test]
+async fn test_dump_and_restore() {
+    let first_loads = Arc::new(AtomicUsize::new(0));
+    let cache = counting_cache(first_loads.clone());
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+
+    let snapshot = cache.dump();
+    drop(cache);
+    assert_eq!(snapshot.entries.len(), 2);
+
+    let second_loads = Arc::new(AtomicUsize::new(0));
+    let restored = counting_cache(second_loads.clone());
+    assert_eq!(restored.restore(snapshot), 2);
+
+    assert_eq!(restored.get(1).await.unwrap(), "loaded_1");
+    assert_eq!(restored.get(2).await.unwrap(), "loaded_2");
+    assert_eq!(second_loads.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_restore_skips_expired_entries() {
+    let cache = counting_cache(Arc::new(AtomicUsize::new(0)));
+    let snapshot = CacheSnapshot {
+        entries: vec![
+            SnapshotEntry {
+                identifier: "1".to_string(),
+                value: "fresh".to_string(),
+                expires_at: Some(SystemTime::now() + Duration::from_secs(60)),
+                soft_expires_at: None,
+                last_accessed: None,
+            },
+            SnapshotEntry {
+                identifier: "2".to_string(),
+                value: "stale".to_string(),
+                expires_at: Some(SystemTime::now() - Duration::from_secs(1)),
+                soft_expires_at: None,
+                last_accessed: None,
+            },
+        ],
+    };
+
+    assert_eq!(cache.restore(snapshot), 1);
+    assert_eq!(cache.size(), 1);
+    assert_eq!(cache.get(1).await.unwrap(), "fresh");
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_snapshot_json_round_trip() {
+    let cache = counting_cache(Arc::new(AtomicUsize::new(0)));
+    cache.get(1).await.unwrap();
+
+    let json = serde_json::to_string(&cache.dump()).unwrap();
+    drop(cache);
+
+    let loads = Arc::new(AtomicUsize::new(0));
+    let restored = counting_cache(loads.clone());
+    let snapshot: CacheSnapshot<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.restore(snapshot), 1);
+
+    assert_eq!(restored.get(1).await.unwrap(), "loaded_1");
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_expiring_json_round_trip() {
+    let item = Expiring::with_soft_ttl(
+        "shared".to_string(),
+        Duration::from_millis(1500),
+        Duration::from_secs(60),
+    );
+
+    let json = serde_json::to_string(&item).unwrap();
+    assert!(json.contains("secs_since_epoch"));
+    let decoded: Expiring<String> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.value, "shared");
+    assert_eq!(decoded.expires_at, item.expires_at);
+    assert_eq!(decoded.soft_expires_at(), item.soft_expires_at());
+
+    let never: Expiring<u32> = serde_json::from_str(r#"{"expires_at":null,"value":7}"#).unwrap();
+    assert_eq!(never.expires_at, None);
+    assert_eq!(never.soft_expires_at(), None);
+}
+
+#[tokio::test]
+async fn test_drain_takes_all_entries() {
+    let cache = counting_cache(Arc::new(AtomicUsize::new(0)));
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    cache.insert(
+        3,
+        Expiring::new(
+            "stale".to_string(),
+            SystemTime::now() - Duration::from_secs(1),
+        ),
+    );
+
+    let mut drained = cache.drain();
+    drained.sort_by(|a, b| a.0.cmp(&b.0));
+    let values: Vec<_> = drained
+        .iter()
+        .map(|(id, item)| (id.as_str(), item.value.as_str()))
+        .collect();
+    assert_eq!(
+        values,
+        vec![("1", "loaded_1"), ("2", "loaded_2"), ("3", "stale")]
+    );
+    assert_eq!(cache.size(), 0);
+    assert!(cache.drain().is_empty());
+}
+
+#[tokio::test]
+async fn test_into_hashmap_takes_live_values() {
+    let cache = counting_cache(Arc::new(AtomicUsize::new(0)));
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    cache.insert(
+        3,
+        Expiring::new(
+            "stale".to_string(),
+            SystemTime::now() - Duration::from_secs(1),
+        ),
+    );
+
+    let map = cache.into_hashmap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["1"], "loaded_1");
+    assert_eq!(map["2"], "loaded_2");
+}
+
+#[tokio::test]
+async fn test_into_hashmap_leaves_other_handles_intact() {
+    let cache = Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(format!("loaded_{}", key))) }),
+        |key: &i32| key.to_string(),
+    );
+    let other = cache.clone();
+    cache.get(1).await.unwrap();
+
+    let map = cache.into_hashmap();
+    assert_eq!(map["1"], "loaded_1");
+    assert_eq!(other.size(), 1);
+    assert_eq!(other.try_peek(&1), Some("loaded_1".to_string()));
+
+    let map = other.into_hashmap();
+    assert_eq!(map.len(), 1);
+}
+
+#[tokio::test]
+async fn test_backing_store_checked_before_loader() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let backing = Arc::new(MemoryBackingStore::new());
+    backing
+        .set("1", &Expiring::never("from_backing".to_string()))
+        .await
+        .unwrap();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            loads_clone.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok(Expiring::never(format!("loaded_{}", key))) })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .backing_store(backing.clone())
+        .build();
+
+    // A local miss is answered by the backing store and cached locally
+    assert_eq!(
+        cache.get_with_source(1).await.unwrap(),
+        ("from_backing".to_string(), ValueSource::Backing)
+    );
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+    assert_eq!(cache.size(), 1);
+
+    // A miss in both levels loads and writes through
+    assert_eq!(cache.get(2).await.unwrap(), "loaded_2");
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    let stored = backing.get("2").await.unwrap().unwrap();
+    assert_eq!(stored.value, "loaded_2");
+
+    // A fresh local cache warms from the backing store
+    cache.clear();
+    assert_eq!(cache.get(2).await.unwrap(), "loaded_2");
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    cache.delete_await(2).await;
+    assert!(backing.get("2").await.unwrap().is_none());
+    assert_eq!(backing.len(), 1);
+}
+
+#[tokio::test]
+async fn test_get_batch_misses_go_through_the_backing_store() {
+    let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let batches_clone = batches.clone();
+    let backing = Arc::new(MemoryBackingStore::new());
+    backing
+        .set("1", &Expiring::never("from_backing".to_string()))
+        .await
+        .unwrap();
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(format!("loaded_{}", key))) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .backing_store(backing.clone())
+        .build()
+        .with_batch_loader(move |keys: Vec<i32>| {
+            batches_clone.lock().unwrap().push(keys.clone());
+            Box::pin(async move {
+                Ok(keys
+                    .into_iter()
+                    .map(|key| {
+                        let ttl = Duration::from_millis(50);
+                        (key, Expiring::with_duration(format!("batch_{}", key), ttl))
+                    })
+                    .collect())
+            })
+        });
+
+    // The backing store answers what it has, and batch-loaded values are written through
+    let results = cache.get_batch(vec![1, 2]).await;
+    assert_eq!(results[0].as_ref().unwrap(), "from_backing");
+    assert_eq!(results[1].as_ref().unwrap(), "batch_2");
+    assert_eq!(*batches.lock().unwrap(), vec![vec![2]]);
+    assert_eq!(backing.get("2").await.unwrap().unwrap().value, "batch_2");
+
+    // Reloading an entry that only just expired counts as a near miss
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        cache.get_batch(vec![2]).await[0].as_ref().unwrap(),
+        "batch_2"
+    );
+    assert_eq!(cache.stats().near_miss_reloads, 1);
+    assert_eq!(batches.lock().unwrap().len(), 2);
+}
+
+/// A backing store whose lookups take far longer than any load timeout
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+struct StalledBackingStore;
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl BackingStore<String> for StalledBackingStore {
+    fn get<'a>(&'a self, _identifier: &'a str) -> BackingFuture<'a, Option<Expiring<String>>> {
+        Box::pin(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(Some(Expiring::never("stalled".to_string())))
+        })
+    }
+
+    fn set<'a>(
+        &'a self,
+        _identifier: &'a str,
+        _item: &'a Expiring<String>,
+    ) -> BackingFuture<'a, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn delete<'a>(&'a self, _identifier: &'a str) -> BackingFuture<'a, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_backing_store_lookup_is_bounded_like_a_load() {
+    let cache = CacheBuilder::new()
+        .loader(|key: i32| Box::pin(async move { Ok(Expiring::never(format!("loaded_{}", key))) }))
+        .key_mapper(|key: &i32| key.to_string())
+        .backing_store(StalledBackingStore)
+        .load_timeout(Duration::from_millis(50))
+        .max_concurrent_loads(1)
+        .build();
+
+    // The stalled lookup gives up its loader slot, so the loader still runs
+    let (value, source) = tokio::time::timeout(Duration::from_secs(5), cache.get_with_source(1))
+        .await
+        .expect("backing store lookup wasn't bounded")
+        .unwrap();
+    assert_eq!(value, "loaded_1");
+    assert_eq!(source, ValueSource::Fresh);
+}
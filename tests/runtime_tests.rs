@@ -0,0 +1,336 @@
+use cache_rs::{Cache, Expiring};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use cache_rs::{
+    CacheBuilder, CacheError, CacheEvent, EvictionReason, ReloadErrorPolicy, ValueSource,
+};
+use std::future::Future;
+use std::pin::pin;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::sync::Arc;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::time::Instant;
+
+/// Polls a future once without any runtime, expecting it to be ready
+fn poll_ready<T>(future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future wasn't ready"),
+    }
+}
+
+#[test]
+fn test_core_operations_without_runtime() {
+    let cache = Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                let value = format!("loaded_{}", key);
+                Ok(Expiring::with_duration(value, Duration::from_secs(10)))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    assert_eq!(poll_ready(cache.get(1)).unwrap(), "loaded_1");
+    cache.insert(2, Expiring::never("manual".to_string()));
+    assert_eq!(poll_ready(cache.get(2)).unwrap(), "manual");
+    assert_eq!(cache.size(), 2);
+
+    cache.delete(1);
+    assert_eq!(cache.size(), 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_janitor_purges_expired_entries() {
+    let cache = Arc::new(Cache::new(
+        |key: i32| {
+            Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_millis(30))) })
+        },
+        |key: &i32| key.to_string(),
+    ));
+    cache.spawn_janitor(Duration::from_millis(20));
+
+    cache.get(1).await.unwrap();
+    cache.get(2).await.unwrap();
+    assert_eq!(cache.size(), 2);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(cache.size(), 0);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_janitor_announces_each_purged_entry() {
+    let expired = Arc::new(AtomicUsize::new(0));
+    let expired_clone = expired.clone();
+    let cache = Arc::new(
+        CacheBuilder::new()
+            .loader(|key: i32| {
+                Box::pin(async move { Ok(Expiring::with_duration(key, Duration::from_millis(30))) })
+            })
+            .key_mapper(|key: &i32| key.to_string())
+            .on_evict(move |_, _, reason| {
+                if reason == EvictionReason::Expired {
+                    expired_clone.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build(),
+    );
+    for key in 1..=3 {
+        cache.get(key).await.unwrap();
+    }
+    cache.insert(4, Expiring::never(4));
+    let mut events = cache.subscribe();
+    cache.spawn_janitor(Duration::from_millis(20));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let mut purged = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        match event {
+            CacheEvent::Expired(identifier) => purged.push(identifier),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+    purged.sort();
+    assert_eq!(purged, vec!["1", "2", "3"]);
+    assert_eq!(expired.load(Ordering::SeqCst), 3);
+    assert_eq!(cache.size(), 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_janitor_stops_when_cache_dropped() {
+    let cache = Arc::new(Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key)) }),
+        |key: &i32| key.to_string(),
+    ));
+    cache.spawn_janitor(Duration::from_millis(10));
+
+    let weak = Arc::downgrade(&cache);
+    drop(cache);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(weak.upgrade().is_none());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_refresh_ahead_reloads_in_background() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let loads = loads_clone.clone();
+            Box::pin(async move {
+                let version = loads.fetch_add(1, Ordering::SeqCst) + 1;
+                if version > 1 {
+                    // Slow reloads show that hits never wait for them
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                let value = format!("{}_v{}", key, version);
+                Ok(Expiring::with_duration(value, Duration::from_millis(400)))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .refresh_ahead(0.5)
+        .build();
+
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+
+    // Outside the refresh window: plain hit
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // Inside the window: the current value comes back without waiting
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    let started = Instant::now();
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+    assert!(started.elapsed() < Duration::from_millis(40));
+
+    // Only one refresh runs for the key, and its result replaces the entry
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.get(1).await.unwrap(), "1_v2");
+}
+
+// The stale hit is revalidated by a background refresh
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_soft_ttl_serves_stale_then_blocks_after_hard_ttl() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Cache::new(
+        move |key: i32| {
+            let version = loads_clone.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                let value = format!("{}_v{}", key, version);
+                Ok(Expiring::with_soft_ttl(
+                    value,
+                    Duration::from_millis(100),
+                    Duration::from_millis(300),
+                ))
+            })
+        },
+        |key: &i32| key.to_string(),
+    );
+
+    // Fresh: plain hit
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // Soft-stale: the stale value is served and reloaded in the background
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(cache.get(1).await.unwrap(), "1_v1");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.get(1).await.unwrap(), "1_v2");
+
+    // Hard-expired: get waits for the reload
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(cache.get(1).await.unwrap(), "1_v3");
+    assert_eq!(loads.load(Ordering::SeqCst), 3);
+}
+
+// Revalidating a stale hit spawns a background refresh
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_get_with_source_reports_each_source() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = CacheBuilder::new()
+        .loader(move |key: i32| {
+            let version = loads_clone.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                if version > 2 {
+                    return Err("backend down".into());
+                }
+                Ok(Expiring::with_soft_ttl(
+                    format!("{}_v{}", key, version),
+                    Duration::from_millis(50),
+                    Duration::from_millis(150),
+                ))
+            })
+        })
+        .key_mapper(|key: &i32| key.to_string())
+        .on_reload_error(ReloadErrorPolicy::StaleFallback {
+            grace: Duration::from_secs(60),
+        })
+        .build();
+
+    let fresh = ("1_v1".to_string(), ValueSource::Fresh);
+    assert_eq!(cache.get_with_source(1).await.unwrap(), fresh);
+    let cached = ("1_v1".to_string(), ValueSource::Cached);
+    assert_eq!(cache.get_with_source(1).await.unwrap(), cached);
+
+    // Past the soft expiry: served while a background reload runs
+    tokio::time::sleep(Duration::from_millis(70)).await;
+    let revalidating = ("1_v1".to_string(), ValueSource::StaleRevalidating);
+    assert_eq!(cache.get_with_source(1).await.unwrap(), revalidating);
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+    // Past the hard expiry with a failing loader: the stale value is the fallback
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let fallback = ("1_v2".to_string(), ValueSource::StaleFallbackOnError);
+    assert_eq!(cache.get_with_source(1).await.unwrap(), fallback);
+}
+
+// The load runs in a spawned task, so its loader needs Tokio's timers
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_detached_load_survives_dropped_caller() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = Arc::new(Cache::new(
+        move |key: i32| {
+            loads_clone.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(Expiring::never(key * 2))
+            })
+        },
+        |key: &i32| key.to_string(),
+    ));
+
+    let (dropped, waiter) = tokio::join!(
+        tokio::time::timeout(Duration::from_millis(20), cache.get_detached(1)),
+        async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            cache.get(1).await
+        }
+    );
+
+    // The first caller gave up, but its load finished for the one still waiting
+    assert!(dropped.is_err());
+    assert_eq!(waiter.unwrap(), 2);
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.stats().coalesced_loads, 1);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_detached_load_reports_dropped_task() {
+    let cache = Arc::new(Cache::new(
+        |key: i32| Box::pin(async move { Ok(Expiring::never(key * 2)) }),
+        |key: &i32| key.to_string(),
+    ));
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // Spawn the load on a runtime that's dropped before it ever runs the task
+    let mut future = Box::pin(cache.get_detached(1));
+    let waker = Waker::noop();
+    {
+        let _enter = runtime.enter();
+        assert!(
+            future
+                .as_mut()
+                .poll(&mut Context::from_waker(waker))
+                .is_pending()
+        );
+    }
+    drop(runtime);
+
+    assert!(matches!(poll_ready(future), Err(CacheError::LoadCancelled)));
+    assert_eq!(cache.size(), 0);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[tokio::test]
+async fn test_shutdown_waits_for_loads_then_rejects_misses() {
+    let cache = Arc::new(Cache::new(
+        |key: i32| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(Expiring::never(key))
+            })
+        },
+        |key: &i32| key.to_string(),
+    ));
+
+    let loading = tokio::spawn({
+        let cache = cache.clone();
+        async move { cache.get(1).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let started = Instant::now();
+    cache.shutdown(Duration::from_secs(5)).await;
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(loading.await.unwrap().unwrap(), 1);
+
+    // The loaded entry is still served, but nothing new is loaded
+    assert_eq!(cache.get(1).await.unwrap(), 1);
+    assert!(matches!(cache.get(2).await, Err(CacheError::ShuttingDown)));
+}
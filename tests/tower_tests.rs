@@ -0,0 +1,62 @@
+#![cfg(feature = "tower")]
+
+use cache_rs::{BoxError, CacheLayer};
+use std::future::{Ready, ready};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Inner service that counts how often it's actually called
+#[derive(Clone)]
+struct Counting {
+    calls: Arc<AtomicUsize>,
+}
+
+impl Service<u32> for Counting {
+    type Response = String;
+    type Error = BoxError;
+    type Future = Ready<Result<String, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: u32) -> Self::Future {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if request == 0 {
+            return ready(Err("bad request".into()));
+        }
+        ready(Ok(format!("response_{}", request)))
+    }
+}
+
+#[tokio::test]
+async fn test_cache_layer_short_circuits_hits() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let layer = CacheLayer::new(Duration::from_secs(60), |request: &u32| request.to_string());
+    let mut service = layer.layer(Counting {
+        calls: calls.clone(),
+    });
+
+    assert_eq!(service.call(1).await.unwrap(), "response_1");
+    assert_eq!(service.call(1).await.unwrap(), "response_1");
+    assert_eq!(service.call(2).await.unwrap(), "response_2");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // Failures reach the caller and aren't cached
+    assert_eq!(
+        service.call(0).await.unwrap_err().to_string(),
+        "bad request"
+    );
+    assert!(service.call(0).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // Clones share the cache
+    let mut clone = service.clone();
+    assert_eq!(clone.call(2).await.unwrap(), "response_2");
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    assert_eq!(service.cache().size(), 2);
+}
@@ -0,0 +1,63 @@
+use cache_rs::{Expiring, LoadFuture, WeakCache};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+fn counting_cache(
+    loads: &Arc<AtomicUsize>,
+) -> WeakCache<u32, String, impl Fn(u32) -> LoadFuture<Arc<String>>> {
+    let loads = loads.clone();
+    WeakCache::new(move |key: u32| {
+        loads.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            Ok(Expiring::with_duration(
+                Arc::new(format!("object_{}", key)),
+                Duration::from_secs(60),
+            ))
+        })
+    })
+}
+
+#[tokio::test]
+async fn test_weak_shares_live_values() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let cache = counting_cache(&loads);
+
+    let first = cache.get(1).await.unwrap();
+    let second = cache.get(1).await.unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_weak_skips_caching_expired_loads() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let loads_clone = loads.clone();
+    let cache = WeakCache::new(move |key: u32| {
+        loads_clone.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(Expiring::with_duration(Arc::new(key), Duration::ZERO)) })
+    });
+
+    let first = cache.get(1).await.unwrap();
+    let second = cache.get(1).await.unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.size(), 0);
+}
+
+#[tokio::test]
+async fn test_weak_reloads_dropped_values() {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let cache = counting_cache(&loads);
+
+    let value = cache.get(1).await.unwrap();
+    assert_eq!(*value, "object_1");
+    drop(value);
+
+    // Nothing else held the value, so the weak reference can't be upgraded
+    assert_eq!(*cache.get(1).await.unwrap(), "object_1");
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+    assert_eq!(cache.purge_expired(), 1);
+    assert_eq!(cache.size(), 0);
+}